@@ -18,7 +18,10 @@ fn main() {
 }
 
 fn input() {
-    let n = 1000u32;
+    // `zkRust prove-sp1 examples/fibonacci -- 500` proves another `n`
+    let n: u32 = zk_rust_io::host_args()
+        .first()
+        .map_or(1000, |n| n.parse().expect("n must be a u32"));
     zk_rust_io::write(&n);
 }
 
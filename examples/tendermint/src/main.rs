@@ -6,19 +6,21 @@ mod utils;
 use zk_rust_io;
 
 pub fn main() {
-    println!("cycle-tracker-start: io");
-    println!("cycle-tracker-start: reading bytes");
-    let encoded_1: Vec<u8> = zk_rust_io::read();
-    let encoded_2: Vec<u8> = zk_rust_io::read();
-    println!("cycle-tracker-end: reading bytes");
-    println!("first 10 bytes: {:?}", &encoded_1[..10]);
-    println!("first 10 bytes: {:?}", &encoded_2[..10]);
+    let (light_block_1, light_block_2) = zk_rust_io::track("io", || {
+        let (encoded_1, encoded_2) = zk_rust_io::track("reading bytes", || {
+            let encoded_1: Vec<u8> = zk_rust_io::read();
+            let encoded_2: Vec<u8> = zk_rust_io::read();
+            (encoded_1, encoded_2)
+        });
+        println!("first 10 bytes: {:?}", &encoded_1[..10]);
+        println!("first 10 bytes: {:?}", &encoded_2[..10]);
 
-    println!("cycle-tracker-start: serde");
-    let light_block_1: LightBlock = serde_cbor::from_slice(&encoded_1).unwrap();
-    let light_block_2: LightBlock = serde_cbor::from_slice(&encoded_2).unwrap();
-    println!("cycle-tracker-end: serde");
-    println!("cycle-tracker-end: io");
+        zk_rust_io::track("serde", || {
+            let light_block_1: LightBlock = serde_cbor::from_slice(&encoded_1).unwrap();
+            let light_block_2: LightBlock = serde_cbor::from_slice(&encoded_2).unwrap();
+            (light_block_1, light_block_2)
+        })
+    });
 
     println!(
         "LightBlock1 number of validators: {}",
@@ -29,36 +31,38 @@ pub fn main() {
         light_block_2.validators.validators().len()
     );
 
-    println!("cycle-tracker-start: header hash");
-    let header_hash_1 = light_block_1.signed_header.header.hash();
-    let header_hash_2 = light_block_2.signed_header.header.hash();
-    println!("cycle-tracker-end: header hash");
+    let (header_hash_1, header_hash_2) = zk_rust_io::track("header hash", || {
+        (
+            light_block_1.signed_header.header.hash(),
+            light_block_2.signed_header.header.hash(),
+        )
+    });
 
-    println!("cycle-tracker-start: public input headers");
-    zk_rust_io::commit(&header_hash_1.as_bytes());
-    zk_rust_io::commit(&header_hash_2.as_bytes());
-    println!("cycle-tracker-end: public input headers");
+    zk_rust_io::track("public input headers", || {
+        zk_rust_io::commit(&header_hash_1.as_bytes());
+        zk_rust_io::commit(&header_hash_2.as_bytes());
+    });
 
-    println!("cycle-tracker-start: verify");
-    let vp = ProdVerifier::default();
-    let opt = Options {
-        trust_threshold: Default::default(),
-        trusting_period: std::time::Duration::from_secs(500),
-        clock_drift: Default::default(),
-    };
-    let verify_time = light_block_2.time() + std::time::Duration::from_secs(20);
-    let verdict = vp.verify_update_header(
-        light_block_2.as_untrusted_state(),
-        light_block_1.as_trusted_state(),
-        &opt,
-        verify_time.unwrap(),
-    );
-    println!("cycle-tracker-end: verify");
+    let verdict = zk_rust_io::track("verify", || {
+        let vp = ProdVerifier::default();
+        let opt = Options {
+            trust_threshold: Default::default(),
+            trusting_period: std::time::Duration::from_secs(500),
+            clock_drift: Default::default(),
+        };
+        let verify_time = light_block_2.time() + std::time::Duration::from_secs(20);
+        vp.verify_update_header(
+            light_block_2.as_untrusted_state(),
+            light_block_1.as_trusted_state(),
+            &opt,
+            verify_time.unwrap(),
+        )
+    });
 
-    println!("cycle-tracker-start: public inputs verdict");
-    let verdict_encoded = serde_cbor::to_vec(&verdict).unwrap();
-    zk_rust_io::commit(&verdict_encoded.as_slice());
-    println!("cycle-tracker-end: public inputs verdict");
+    zk_rust_io::track("public inputs verdict", || {
+        let verdict_encoded = serde_cbor::to_vec(&verdict).unwrap();
+        zk_rust_io::commit(&verdict_encoded.as_slice());
+    });
 
     match verdict {
         Verdict::Success => {
@@ -82,13 +86,24 @@ pub fn input() {
 
 pub fn output() {
     let (light_block_1, light_block_2) = get_light_blocks();
-    // Verify the public values
-    let mut expected_public_values: Vec<u8> = Vec::new();
-    expected_public_values.extend(light_block_1.signed_header.header.hash().as_bytes());
-    expected_public_values.extend(light_block_2.signed_header.header.hash().as_bytes());
-    expected_public_values.extend(serde_cbor::to_vec(&expected_verdict).unwrap());
+    let expected_verdict = verify_blocks(light_block_1.clone(), light_block_2.clone());
 
-    let public_inputs: Vec<u8> = zk_rust_io::out();
+    // Verify the public values, read in the order they were committed
+    let header_hash_1: Vec<u8> = zk_rust_io::out();
+    let header_hash_2: Vec<u8> = zk_rust_io::out();
+    let verdict_encoded: Vec<u8> = zk_rust_io::out();
 
-    assert_eq!(public_inputs.as_ref(), expected_public_values);
+    assert_eq!(
+        header_hash_1,
+        light_block_1.signed_header.header.hash().as_bytes()
+    );
+    assert_eq!(
+        header_hash_2,
+        light_block_2.signed_header.header.hash().as_bytes()
+    );
+    assert_eq!(
+        verdict_encoded,
+        serde_cbor::to_vec(&expected_verdict).unwrap()
+    );
+    println!("Verified light block headers and verdict");
 }
@@ -0,0 +1,190 @@
+use aligned_sdk::core::errors::AlignedError;
+use clap::{Args, Subcommand};
+use log::info;
+use serde::Serialize;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::mock_batcher::MockBatcherArgs;
+use crate::network;
+use crate::record::{self, SubmissionRecord};
+use crate::SubmissionArgs;
+
+// Delay between two checks of the nonce of a proof the batcher did not answer for
+const BATCH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Args, Debug)]
+pub struct AlignedArgs {
+    #[command(subcommand)]
+    pub command: AlignedCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AlignedCommand {
+    #[clap(about = "Run a local batcher and RPC node that verify submitted proofs offline")]
+    MockBatcher(MockBatcherArgs),
+    #[clap(about = "Look up a submitted proof by its commitment and resume waiting for it")]
+    Status(Box<StatusArgs>),
+}
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    #[clap(
+        help = "Proof commitment of the submission, or a prefix of it, or the path of its record"
+    )]
+    pub proof: String,
+    #[clap(
+        long = "proof-data-path",
+        default_value = "./proof_data",
+        help = "Directory searched for the submission records"
+    )]
+    pub proof_data_directory_path: PathBuf,
+    #[clap(
+        long = "wait",
+        help = "Wait for the proof to be batched and verified, for at most --verification-timeout"
+    )]
+    pub wait: bool,
+    #[command(flatten)]
+    pub aligned: SubmissionArgs,
+}
+
+/// Progress of a proof sent to the batcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionState {
+    /// Sent to the batcher, not paid for in a batch yet
+    Pending,
+    /// Paid for in a batch, but the answer of the batcher was lost so its batch is unknown
+    Batched,
+    /// In a batch that is not verified yet
+    Included,
+    Verified,
+}
+
+/// Result of `aligned status`.
+#[derive(Debug, Serialize)]
+pub struct StatusOutput {
+    pub record: PathBuf,
+    pub state: SubmissionState,
+    pub explorer_url: Option<String>,
+    pub submission: SubmissionRecord,
+}
+
+pub async fn status(args: &StatusArgs) -> io::Result<StatusOutput> {
+    let record_path = find_record(&args.proof, &args.proof_data_directory_path)?;
+    let mut record = SubmissionRecord::read(&record_path)?;
+    if record.network != args.aligned.network {
+        return Err(io::Error::other(format!(
+            "Proof {:?} was submitted to `{}`, pass --network {}",
+            record.proof, record.network, record.network
+        )));
+    }
+    let network = network::resolve(&args.aligned.network, &args.aligned.network_profiles)?;
+    let rpc_url = &args.aligned.rpc_url;
+    let timeout = Duration::from_secs(args.aligned.verification_timeout);
+    let aligned_error = |e: AlignedError| io::Error::other(e.to_string());
+
+    let state = match record.aligned_verification_data()? {
+        Some(aligned_verification_data) => {
+            let verified = if args.wait {
+                network
+                    .wait_for_verification(rpc_url, &aligned_verification_data, timeout)
+                    .await
+                    .map_err(aligned_error)?;
+                true
+            } else {
+                network
+                    .is_verified(rpc_url, &aligned_verification_data)
+                    .await
+                    .map_err(aligned_error)?
+            };
+            if verified && !record.verified {
+                record.verified = true;
+                record.write(&record_path)?;
+            }
+            if verified {
+                SubmissionState::Verified
+            } else {
+                SubmissionState::Included
+            }
+        }
+        // The nonce of the proof is only used up once a batch paid for it
+        None => {
+            let deadline = Instant::now() + timeout;
+            loop {
+                let next_nonce = network
+                    .next_nonce(rpc_url, record.proof_generator_addr)
+                    .await
+                    .map_err(aligned_error)?;
+                if next_nonce > record.nonce {
+                    break SubmissionState::Batched;
+                }
+                if !args.wait || Instant::now() + BATCH_CHECK_INTERVAL > deadline {
+                    break SubmissionState::Pending;
+                }
+                info!(
+                    "Proof not batched yet, checking again in {}s",
+                    BATCH_CHECK_INTERVAL.as_secs()
+                );
+                tokio::time::sleep(BATCH_CHECK_INTERVAL).await;
+            }
+        }
+    };
+
+    let explorer_url = record
+        .inclusion
+        .as_ref()
+        .and_then(|inclusion| network.explorer_url(&inclusion.batch_merkle_root));
+    match state {
+        SubmissionState::Pending => info!(
+            "Proof {:?} was sent with nonce {} and is not batched yet",
+            record.proof, record.nonce
+        ),
+        SubmissionState::Batched => info!(
+            "Proof {:?} was batched, the batcher did not answer with its batch",
+            record.proof
+        ),
+        SubmissionState::Included => info!(
+            "Proof {:?} is in a batch not verified yet, wait for it with --wait",
+            record.proof
+        ),
+        SubmissionState::Verified => info!("Proof {:?} verified on Aligned", record.proof),
+    }
+    if let Some(explorer_url) = &explorer_url {
+        info!("{}", explorer_url);
+    }
+    Ok(StatusOutput {
+        record: record_path,
+        state,
+        explorer_url,
+        submission: record,
+    })
+}
+
+// `proof` is either the path of a record or a prefix of the commitment of the proof it records.
+fn find_record(proof: &str, proof_data_dir: &Path) -> io::Result<PathBuf> {
+    if Path::new(proof).is_file() {
+        return Ok(PathBuf::from(proof));
+    }
+    let mut found = vec![];
+    for path in record::find_records(proof_data_dir)? {
+        if SubmissionRecord::read(&path)?.matches(proof) {
+            found.push(path);
+        }
+    }
+    match found.len() {
+        0 => Err(io::Error::other(format!(
+            "No submission record of proof {} in {}",
+            proof,
+            proof_data_dir.display()
+        ))),
+        1 => Ok(found.remove(0)),
+        count => Err(io::Error::other(format!(
+            "{} submission records match {}, give a longer commitment",
+            count, proof
+        ))),
+    }
+}
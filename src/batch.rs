@@ -0,0 +1,269 @@
+use clap::Args;
+use log::{error, info};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::output::{ProofArtifacts, ProofOutput};
+use crate::{Backend, ProofArgs};
+
+/// Environment variable holding the input files proven by the host, one proof per file.
+pub const INPUTS_ENV: &str = "ZKRUST_INPUTS";
+
+#[derive(Args, Debug)]
+pub struct ProveArgs {
+    #[clap(
+        long = "backend",
+        value_enum,
+        help = "zkVM the program is proven with, defaults to `backend` in zkrust.toml"
+    )]
+    pub backend: Option<Backend>,
+    #[clap(
+        long = "inputs-dir",
+        conflicts_with = "input",
+        help = "Directory of input files, the program is built once and proven over each file"
+    )]
+    pub inputs_dir: Option<PathBuf>,
+    #[command(flatten)]
+    pub proof: ProofArgs,
+}
+
+/// Outcome of one input file of a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchProofStatus {
+    Proven,
+    Failed,
+}
+
+/// Proof of one input file of a batch.
+#[derive(Debug, Serialize)]
+pub struct BatchProof {
+    pub input: PathBuf,
+    pub status: BatchProofStatus,
+    /// Missing when the host failed to prove this input
+    pub proof: Option<ProofOutput>,
+    /// Why the input was not proven
+    pub error: Option<String>,
+}
+
+/// Result of `prove --inputs-dir`.
+#[derive(Debug, Serialize)]
+pub struct BatchOutput {
+    pub backend: Backend,
+    pub proofs: Vec<BatchProof>,
+    /// Inputs not proven, zkRust exits with an error when there are any
+    pub failed: usize,
+}
+
+/// Input files of `inputs_dir` in name order. Proofs are written to a directory named after
+/// each file, so names must differ once the extension is removed.
+pub fn input_files(inputs_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(inputs_dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path.canonicalize()?);
+        }
+    }
+    files.sort();
+    if files.is_empty() {
+        return Err(io::Error::other(format!(
+            "No input files in {}",
+            inputs_dir.display()
+        )));
+    }
+    let mut names = HashSet::new();
+    for file in &files {
+        if !names.insert(proof_name(file)) {
+            return Err(io::Error::other(format!(
+                "Several input files are named {:?}, their proofs would overwrite each other",
+                proof_name(file)
+            )));
+        }
+    }
+    Ok(files)
+}
+
+/// Directory the host writes the proof of `input` to.
+pub fn proof_dir(proof_data_dir: &Path, backend: Backend, input: &Path) -> PathBuf {
+    proof_data_dir.join(proof_name(input)).join(backend.name())
+}
+
+/// Host environment listing the input files to prove.
+pub fn inputs_env(files: &[PathBuf]) -> io::Result<(&'static str, String)> {
+    let inputs = std::env::join_paths(files).map_err(io::Error::other)?;
+    let inputs = inputs
+        .into_string()
+        .map_err(|_| io::Error::other("Input file paths must be valid UTF-8"))?;
+    Ok((INPUTS_ENV, inputs))
+}
+
+/// Collects the proofs written by the host for each input file.
+pub fn collect(backend: Backend, proof_data_dir: &Path, files: &[PathBuf]) -> BatchOutput {
+    let name = backend.name();
+    let proofs = files
+        .iter()
+        .map(|input| {
+            let dir = proof_dir(proof_data_dir, backend, input);
+            let artifact = |extension: &str| dir.join(format!("{}.{}", name, extension));
+            let program = match backend {
+                Backend::Sp1 => artifact("elf"),
+                Backend::Risc0 => artifact("imageid"),
+            };
+            let program_id = match backend {
+                Backend::Sp1 => artifact("vkey"),
+                Backend::Risc0 => artifact("imageid"),
+            };
+            // The host records why a run failed, it stops without a record when it crashes
+            if let Ok(error) = fs::read_to_string(artifact("error")) {
+                return BatchProof {
+                    input: input.clone(),
+                    status: BatchProofStatus::Failed,
+                    proof: None,
+                    error: Some(error),
+                };
+            }
+            if !artifact("proof").exists() {
+                return BatchProof {
+                    input: input.clone(),
+                    status: BatchProofStatus::Failed,
+                    proof: None,
+                    error: Some("The host stopped before proving this input".to_string()),
+                };
+            }
+            let proof = ProofOutput::from_artifacts(
+                name,
+                ProofArtifacts::new(
+                    artifact("proof"),
+                    program,
+                    artifact("pub"),
+                    artifact("public_inputs.json"),
+                ),
+                &program_id,
+                &artifact("cycles"),
+            );
+            BatchProof {
+                input: input.clone(),
+                status: match proof {
+                    Ok(_) => BatchProofStatus::Proven,
+                    Err(_) => BatchProofStatus::Failed,
+                },
+                error: proof.as_ref().err().map(|e| e.to_string()),
+                proof: proof.ok(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let failed = proofs
+        .iter()
+        .filter(|proof| proof.status == BatchProofStatus::Failed)
+        .count();
+    BatchOutput {
+        backend,
+        proofs,
+        failed,
+    }
+}
+
+/// Logs one line per input with its status and cycle count.
+pub fn log_summary(output: &BatchOutput) {
+    let width = output
+        .proofs
+        .iter()
+        .map(|proof| proof_name(&proof.input).len())
+        .max()
+        .unwrap_or_default()
+        .max("input".len());
+    info!("{:<width$}  {:<7}  {:>12}", "input", "status", "cycles");
+    for proof in &output.proofs {
+        let (status, cycles) = match &proof.proof {
+            Some(proof) => (
+                "proven",
+                proof
+                    .cycles
+                    .map(|cycles| cycles.to_string())
+                    .unwrap_or_default(),
+            ),
+            None => ("failed", String::new()),
+        };
+        info!(
+            "{:<width$}  {:<7}  {:>12}",
+            proof_name(&proof.input),
+            status,
+            cycles
+        );
+    }
+    for proof in &output.proofs {
+        if let Some(error) = &proof.error {
+            error!("{} failed: {}", proof_name(&proof.input), error.trim_end());
+        }
+    }
+    info!(
+        "{} of {} input(s) proven",
+        output.proofs.len() - output.failed,
+        output.proofs.len()
+    );
+}
+
+fn proof_name(input: &Path) -> String {
+    input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_proven_and_failed_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = ["a.json", "b.json", "c.json"]
+            .iter()
+            .map(|name| dir.path().join("inputs").join(name))
+            .collect();
+        let proof_data_dir = dir.path().join("proof_data");
+
+        let proven = proof_dir(&proof_data_dir, Backend::Sp1, &files[0]);
+        fs::create_dir_all(&proven).unwrap();
+        fs::write(proven.join("sp1.proof"), b"proof").unwrap();
+        fs::write(proven.join("sp1.pub"), [1, 2]).unwrap();
+        fs::write(proven.join("sp1.cycles"), "42").unwrap();
+        let failed = proof_dir(&proof_data_dir, Backend::Sp1, &files[1]);
+        fs::create_dir_all(&failed).unwrap();
+        fs::write(failed.join("sp1.error"), "Input 0 is not a u32").unwrap();
+
+        let output = collect(Backend::Sp1, &proof_data_dir, &files);
+        assert_eq!(output.failed, 2);
+        let statuses: Vec<BatchProofStatus> =
+            output.proofs.iter().map(|proof| proof.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                BatchProofStatus::Proven,
+                BatchProofStatus::Failed,
+                BatchProofStatus::Failed
+            ]
+        );
+        let proof = output.proofs[0].proof.as_ref().unwrap();
+        assert_eq!(proof.cycles, Some(42));
+        assert_eq!(proof.public_values.as_deref(), Some("0102"));
+        assert_eq!(
+            output.proofs[1].error.as_deref(),
+            Some("Input 0 is not a u32")
+        );
+        assert_eq!(
+            output.proofs[2].error.as_deref(),
+            Some("The host stopped before proving this input")
+        );
+
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["failed"], 2);
+        assert_eq!(json["proofs"][0]["status"], "proven");
+        assert_eq!(json["proofs"][1]["status"], "failed");
+    }
+}
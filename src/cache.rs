@@ -0,0 +1,182 @@
+use clap::{Args, Subcommand};
+use log::info;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{risc0, sp1, Backend};
+
+/// Directory of the cache relative to the zkRust home directory
+pub const CACHE_DIR: &str = "cache";
+/// Environment variable holding the cache entry the host reads and writes its artifacts in.
+pub const CACHE_DIR_ENV: &str = "ZKRUST_CACHE_DIR";
+const SP1_SKIP_BUILD_ENV: &str = "ZKRUST_SKIP_PROGRAM_BUILD";
+const RISC0_SKIP_BUILD_ENV: &str = "RISC0_SKIP_BUILD";
+
+// Build outputs of the guest, they do not change what is built.
+const IGNORED_ENTRIES: &[&str] = &["target", "elf"];
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    #[clap(about = "Remove the cached ELFs, proving keys and image IDs")]
+    Clean,
+}
+
+/// Result of the `cache clean` command.
+#[derive(Debug, Serialize)]
+pub struct CacheCleanOutput {
+    pub path: PathBuf,
+    pub removed: bool,
+}
+
+/// Cache entry of the guest in the backend workspace. Entries are keyed by the hash of the
+/// guest sources, its merged manifest and lockfiles, the pinned backend versions and the zkRust
+/// version.
+#[derive(Debug)]
+pub struct ProgramCache {
+    pub backend: Backend,
+    pub dir: PathBuf,
+}
+
+impl ProgramCache {
+    pub fn new(home_dir: &Path, backend: Backend, guest_path: &Path) -> io::Result<Self> {
+        let (guest_dir, base_host_cargo_toml) = match backend {
+            Backend::Sp1 => (sp1::SP1_SRC_DIR, sp1::SP1_BASE_HOST_CARGO_TOML),
+            Backend::Risc0 => (risc0::RISC0_SRC_DIR, risc0::RISC0_BASE_HOST_CARGO_TOML),
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(fs::read(home_dir.join(base_host_cargo_toml))?);
+        // The lockfile of the workspace is hashed with the guest, the one of the project holds
+        // the versions a `cargo update` of the project resolved
+        let project_lockfile = guest_path.join("Cargo.lock");
+        if project_lockfile.exists() {
+            hasher.update(fs::read(project_lockfile)?);
+        }
+        hash_dir(&mut hasher, &home_dir.join(guest_dir), Path::new(""))?;
+        let hash = hex::encode(hasher.finalize());
+        Ok(ProgramCache {
+            backend,
+            dir: home_dir.join(CACHE_DIR).join(backend.name()).join(hash),
+        })
+    }
+
+    /// Whether a previous run left every artifact needed to skip the build and setup.
+    pub fn is_built(&self) -> bool {
+        let artifacts: &[&str] = match self.backend {
+            Backend::Sp1 => &["sp1.elf", "sp1.vk", "sp1.pk"],
+            Backend::Risc0 => &["risc0.elf", "risc0.imageid"],
+        };
+        artifacts
+            .iter()
+            .all(|artifact| self.dir.join(artifact).exists())
+    }
+
+    /// Creates the cache entry and, when it is complete, restores the ELF so the guest is not
+    /// rebuilt. Returns the environment of the host.
+    pub fn prepare(&self, home_dir: &Path) -> io::Result<Vec<(&'static str, String)>> {
+        fs::create_dir_all(&self.dir)?;
+        let mut envs = vec![(CACHE_DIR_ENV, self.dir.display().to_string())];
+        if !self.is_built() {
+            return Ok(envs);
+        }
+        info!(
+            "Program unchanged, using the cached build in {:?}",
+            self.dir
+        );
+        match self.backend {
+            // The SP1 host embeds the ELF at compile time
+            Backend::Sp1 => {
+                let elf = home_dir.join(sp1::SP1_GUEST_ELF);
+                if let Some(elf_dir) = elf.parent() {
+                    fs::create_dir_all(elf_dir)?;
+                }
+                fs::copy(self.dir.join("sp1.elf"), elf)?;
+                envs.push((SP1_SKIP_BUILD_ENV, "1".to_string()));
+            }
+            // The RISC0 host loads the ELF and image ID from the cache when it runs
+            Backend::Risc0 => envs.push((RISC0_SKIP_BUILD_ENV, "1".to_string())),
+        }
+        Ok(envs)
+    }
+}
+
+/// Removes every cache entry.
+pub fn clean(home_dir: &Path) -> io::Result<CacheCleanOutput> {
+    let path = home_dir.join(CACHE_DIR);
+    let removed = path.exists();
+    if removed {
+        fs::remove_dir_all(&path)?;
+    }
+    Ok(CacheCleanOutput { path, removed })
+}
+
+// Hashes relative paths along with contents so moving a file changes the hash.
+fn hash_dir(hasher: &mut Sha256, dir: &Path, relative: &Path) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if IGNORED_ENTRIES
+            .iter()
+            .any(|name| entry.file_name() == *name)
+        {
+            continue;
+        }
+        let relative = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            hash_dir(hasher, &entry.path(), &relative)?;
+        } else {
+            let contents = fs::read(entry.path())?;
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update((contents.len() as u64).to_le_bytes());
+            hasher.update(contents);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_entries_by_the_resolved_dependencies() {
+        let home = tempfile::tempdir().unwrap();
+        let guest_dir = home.path().join(sp1::SP1_SRC_DIR);
+        fs::create_dir_all(guest_dir.join("src")).unwrap();
+        fs::create_dir_all(guest_dir.join("target")).unwrap();
+        fs::write(guest_dir.join("src/main.rs"), "fn main() {}").unwrap();
+        let base_host_cargo_toml = home.path().join(sp1::SP1_BASE_HOST_CARGO_TOML);
+        fs::create_dir_all(base_host_cargo_toml.parent().unwrap()).unwrap();
+        fs::write(base_host_cargo_toml, "[package]").unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let entry = || {
+            ProgramCache::new(home.path(), Backend::Sp1, project.path())
+                .unwrap()
+                .dir
+        };
+
+        let unchanged = entry();
+        fs::write(guest_dir.join("target/build.log"), "built").unwrap();
+        assert_eq!(entry(), unchanged);
+
+        fs::write(guest_dir.join("Cargo.lock"), "version = 3").unwrap();
+        let guest_locked = entry();
+        assert_ne!(guest_locked, unchanged);
+
+        fs::write(project.path().join("Cargo.lock"), "version = 3").unwrap();
+        let project_locked = entry();
+        assert_ne!(project_locked, guest_locked);
+        fs::write(project.path().join("Cargo.lock"), "version = 4").unwrap();
+        assert_ne!(entry(), project_locked);
+    }
+}
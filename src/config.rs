@@ -0,0 +1,436 @@
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use log::info;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::input::InputFormat;
+use crate::network::{self, AlignedNetwork, NetworkProfile};
+use crate::signer::SignerSource;
+use crate::{Backend, ProofArgs, ProofType, SubmissionArgs};
+
+/// Configuration file of a project, next to its `Cargo.toml`, or of the user in the zkRust home.
+pub const CONFIG_FILE: &str = "zkrust.toml";
+
+// Ids of the `ProofArgs` and `SubmissionArgs` flags, as named in their clap attributes
+const RPC_URL_ARG: &str = "URL of an Ethereum RPC Node";
+const NETWORK_ARG: &str = "The working network's name";
+const BATCHER_URL_ARG: &str = "URL of the Aligned Batcher";
+const BATCHER_PAYMENT_ARG: &str =
+    "Payment send to the BatcherServicContract to fund Proof submission (Wei)";
+const KEYSTORE_PATH_ARG: &str = "Path to Wallet Key Store";
+const PRECOMPILES_ARG: &str = "Enables zkVM Acceleration via VM Precompiles";
+const VERIFICATION_DATA_PATH_ARG: &str = "Aligned verification data directory Path";
+const PROOF_DATA_PATH_ARG: &str = "Proof data directory path";
+const INPUT_ARG: &str = "input";
+const INPUT_FORMAT_ARG: &str = "input_format";
+const PROOF_TYPE_ARG: &str = "proof_type";
+const RETRIES_ARG: &str = "retries";
+const SIGNER_ARG: &str = "signer";
+const DERIVATION_PATH_ARG: &str = "derivation_path";
+const SUBMIT_TIMEOUT_ARG: &str = "submit_timeout";
+const VERIFICATION_TIMEOUT_ARG: &str = "verification_timeout";
+
+/// Defaults of the command line flags. Keys are named after the flags, e.g. `rpc-url`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// zkVM used by `prove`, `profile` and `precompiles` when `--backend` is omitted
+    pub backend: Option<String>,
+    pub input: Option<String>,
+    pub input_format: Option<String>,
+    pub precompiles: Option<bool>,
+    pub proof_data_path: Option<String>,
+    pub proof_type: Option<String>,
+    pub rpc_url: Option<String>,
+    pub network: Option<String>,
+    pub batcher_url: Option<String>,
+    pub batcher_payment: Option<u64>,
+    pub keystore_path: Option<PathBuf>,
+    pub signer: Option<SignerSource>,
+    pub derivation_path: Option<String>,
+    pub aligned_verification_data_path: Option<String>,
+    pub retries: Option<u32>,
+    /// Seconds, as `--submit-timeout`
+    pub submit_timeout: Option<u64>,
+    /// Seconds, as `--verification-timeout`
+    pub verification_timeout: Option<u64>,
+    /// Aligned deployments `network` can name besides the aligned-sdk ones
+    pub networks: BTreeMap<String, NetworkProfile>,
+}
+
+impl Config {
+    /// Reads the user configuration in `home_dir`, overridden by the configuration of the
+    /// project at `guest_path`. Both are optional.
+    pub fn load(home_dir: &Path, guest_path: &Path) -> io::Result<Config> {
+        let user = Config::read(home_dir)?.unwrap_or_default();
+        Ok(match Config::read(guest_path)? {
+            Some(project) => user.merge(project),
+            None => user,
+        })
+    }
+
+    // Relative paths are resolved from the directory of the configuration file.
+    fn read(dir: &Path) -> io::Result<Option<Config>> {
+        let path = dir.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        info!("Reading defaults from {}", path.display());
+        let mut config: Config = toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| io::Error::other(format!("Invalid {}: {}", path.display(), e)))?;
+        if let Some(input) = config.input.as_mut().filter(|input| *input != "-") {
+            *input = dir.join(&*input).display().to_string();
+        }
+        if let Some(keystore_path) = config.keystore_path.as_mut() {
+            *keystore_path = dir.join(&*keystore_path);
+        }
+        if let Some(proof_data_path) = config.proof_data_path.as_mut() {
+            *proof_data_path = dir.join(&*proof_data_path).display().to_string();
+        }
+        config.signer = config.signer.map(|signer| signer.relative_to(dir));
+        Ok(Some(config))
+    }
+
+    fn merge(mut self, project: Config) -> Config {
+        // Profiles of the project replace the user profiles of the same name
+        self.networks.extend(project.networks);
+        Config {
+            backend: project.backend.or(self.backend),
+            input: project.input.or(self.input),
+            input_format: project.input_format.or(self.input_format),
+            precompiles: project.precompiles.or(self.precompiles),
+            proof_data_path: project.proof_data_path.or(self.proof_data_path),
+            proof_type: project.proof_type.or(self.proof_type),
+            rpc_url: project.rpc_url.or(self.rpc_url),
+            network: project.network.or(self.network),
+            batcher_url: project.batcher_url.or(self.batcher_url),
+            batcher_payment: project.batcher_payment.or(self.batcher_payment),
+            keystore_path: project.keystore_path.or(self.keystore_path),
+            signer: project.signer.or(self.signer),
+            derivation_path: project.derivation_path.or(self.derivation_path),
+            aligned_verification_data_path: project
+                .aligned_verification_data_path
+                .or(self.aligned_verification_data_path),
+            retries: project.retries.or(self.retries),
+            submit_timeout: project.submit_timeout.or(self.submit_timeout),
+            verification_timeout: project.verification_timeout.or(self.verification_timeout),
+            networks: self.networks,
+        }
+    }
+
+    /// Sets the flags of `args` that were not given on the command line.
+    pub fn apply(&self, args: &mut ProofArgs, matches: &ArgMatches) -> io::Result<()> {
+        self.apply_submission(&mut args.aligned, matches)?;
+        if let Some(precompiles) = self.precompiles.filter(|_| unset(matches, PRECOMPILES_ARG)) {
+            args.precompiles = precompiles;
+        }
+        if let Some(path) = self
+            .proof_data_path
+            .clone()
+            .filter(|_| unset(matches, PROOF_DATA_PATH_ARG))
+        {
+            args.proof_data_directory_path = path;
+        }
+        if let Some(proof_type) = self
+            .proof_type
+            .as_deref()
+            .filter(|_| unset(matches, PROOF_TYPE_ARG))
+        {
+            args.proof_type = Some(parse_value::<ProofType>("proof-type", proof_type)?);
+        }
+        if let Some(input) = self.input.clone().filter(|_| unset(matches, INPUT_ARG)) {
+            args.input = Some(input);
+        }
+        if let Some(format) = self
+            .input_format
+            .as_deref()
+            .filter(|_| unset(matches, INPUT_FORMAT_ARG))
+        {
+            args.input_format = Some(parse_value::<InputFormat>("input-format", format)?);
+        }
+        if args.submit_to_aligned
+            && args.aligned.signer == SignerSource::Keystore
+            && args.aligned.keystore_path.is_none()
+        {
+            return Err(io::Error::other(
+                "--submit-to-aligned needs --keystore-path or `keystore-path` in zkrust.toml, or another --signer",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sets the wallet and network flags of `args` that were not given on the command line.
+    pub fn apply_submission(
+        &self,
+        args: &mut SubmissionArgs,
+        matches: &ArgMatches,
+    ) -> io::Result<()> {
+        if let Some(rpc_url) = self.rpc_url.clone().filter(|_| unset(matches, RPC_URL_ARG)) {
+            args.rpc_url = rpc_url;
+        }
+        if let Some(network) = self.network.clone().filter(|_| unset(matches, NETWORK_ARG)) {
+            args.network = network;
+        }
+        if let Some(batcher_url) = self
+            .batcher_url
+            .clone()
+            .filter(|_| unset(matches, BATCHER_URL_ARG))
+        {
+            args.batcher_url = batcher_url;
+        }
+        if let Some(payment) = self
+            .batcher_payment
+            .filter(|_| unset(matches, BATCHER_PAYMENT_ARG))
+        {
+            args.batcher_payment = payment.into();
+        }
+        if let Some(keystore_path) = self
+            .keystore_path
+            .clone()
+            .filter(|_| unset(matches, KEYSTORE_PATH_ARG))
+        {
+            args.keystore_path = Some(keystore_path);
+        }
+        if let Some(path) = self
+            .aligned_verification_data_path
+            .clone()
+            .filter(|_| unset(matches, VERIFICATION_DATA_PATH_ARG))
+        {
+            args.batch_inclusion_data_directory_path = path;
+        }
+        if let Some(signer) = self.signer.clone().filter(|_| unset(matches, SIGNER_ARG)) {
+            args.signer = signer;
+        }
+        if let Some(derivation_path) = self
+            .derivation_path
+            .clone()
+            .filter(|_| unset(matches, DERIVATION_PATH_ARG))
+        {
+            args.derivation_path = derivation_path;
+        }
+        if let Some(retries) = self.retries.filter(|_| unset(matches, RETRIES_ARG)) {
+            args.retries = retries;
+        }
+        if let Some(timeout) = self
+            .submit_timeout
+            .filter(|_| unset(matches, SUBMIT_TIMEOUT_ARG))
+        {
+            args.submit_timeout = timeout;
+        }
+        if let Some(timeout) = self
+            .verification_timeout
+            .filter(|_| unset(matches, VERIFICATION_TIMEOUT_ARG))
+        {
+            args.verification_timeout = timeout;
+        }
+        args.network_profiles = self.networks.clone();
+        // The profile of the network replaces the defaults of its URLs, not the flags
+        if let AlignedNetwork::Profile { profile, .. } =
+            network::resolve(&args.network, &args.network_profiles)?
+        {
+            if let Some(rpc_url) = profile.rpc_url.filter(|_| unset(matches, RPC_URL_ARG)) {
+                args.rpc_url = rpc_url;
+            }
+            if let Some(batcher_url) = profile
+                .batcher_url
+                .filter(|_| unset(matches, BATCHER_URL_ARG))
+            {
+                args.batcher_url = batcher_url;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets `backend` to the `backend` of the configuration when `--backend` was omitted.
+    pub fn apply_backend(&self, backend: &mut Option<Backend>) -> io::Result<()> {
+        if let (None, Some(configured)) = (&backend, &self.backend) {
+            *backend = Some(parse_value("backend", configured)?);
+        }
+        Ok(())
+    }
+}
+
+// Flags set from the environment count as given on the command line
+fn unset(matches: &ArgMatches, id: &str) -> bool {
+    !matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+/// Error of commands run without `--backend` nor a configured `backend`.
+pub fn missing_backend() -> io::Error {
+    io::Error::other("--backend is required, or set `backend` in zkrust.toml")
+}
+
+fn parse_value<T: ValueEnum>(key: &str, value: &str) -> io::Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        io::Error::other(format!(
+            "Invalid `{}` in zkrust.toml: {}, expected one of {}",
+            key,
+            value,
+            T::value_variants()
+                .iter()
+                .filter_map(|variant| variant.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Args, Command, FromArgMatches};
+
+    const SERVICES: &str = r#"
+batcher-payment-service = "0x7969c5eD335650692Bc04293B07F5BF2e7A673C0"
+aligned-service-manager = "0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8"
+"#;
+
+    fn parse(flags: &[&str]) -> (ProofArgs, ArgMatches) {
+        let matches = ProofArgs::augment_args(Command::new("prove"))
+            .try_get_matches_from(["prove", "program"].iter().chain(flags))
+            .unwrap();
+        (ProofArgs::from_arg_matches(&matches).unwrap(), matches)
+    }
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn project_config_overrides_user_config() {
+        let user = config(&format!(
+            r#"
+rpc-url = "http://user:8545"
+network = "devnet"
+proof-type = "core"
+retries = 5
+
+[networks.local]
+batcher-url = "ws://user:8080"
+{SERVICES}
+[networks.staging]
+batcher-url = "ws://staging:8080"
+{SERVICES}"#
+        ));
+        let project = config(&format!(
+            r#"
+rpc-url = "http://project:8545"
+proof-type = "compressed"
+
+[networks.local]
+batcher-url = "ws://project:8080"
+{SERVICES}"#
+        ));
+        let merged = user.merge(project);
+        assert_eq!(merged.rpc_url.as_deref(), Some("http://project:8545"));
+        assert_eq!(merged.proof_type.as_deref(), Some("compressed"));
+        assert_eq!(merged.network.as_deref(), Some("devnet"));
+        assert_eq!(merged.retries, Some(5));
+        assert_eq!(
+            merged.networks["local"].batcher_url.as_deref(),
+            Some("ws://project:8080")
+        );
+        assert_eq!(
+            merged.networks["staging"].batcher_url.as_deref(),
+            Some("ws://staging:8080")
+        );
+    }
+
+    #[test]
+    fn config_sets_the_flags_not_given() {
+        let config = config(
+            r#"
+rpc-url = "http://config:8545"
+retries = 7
+precompiles = true
+proof-data-path = "/tmp/proofs"
+proof-type = "core"
+input-format = "cbor"
+"#,
+        );
+        let (mut args, matches) = parse(&["--rpc-url", "http://flag:8545", "--retries", "1"]);
+        config.apply(&mut args, &matches).unwrap();
+        assert_eq!(args.aligned.rpc_url, "http://flag:8545");
+        assert_eq!(args.aligned.retries, 1);
+        assert!(args.precompiles);
+        assert_eq!(args.proof_data_directory_path, "/tmp/proofs");
+        assert_eq!(args.proof_type, Some(ProofType::Core));
+        assert_eq!(args.input_format, Some(InputFormat::Cbor));
+
+        let (mut args, matches) = parse(&["--proof-type", "compressed"]);
+        config.apply(&mut args, &matches).unwrap();
+        assert_eq!(args.aligned.rpc_url, "http://config:8545");
+        assert_eq!(args.aligned.retries, 7);
+        assert_eq!(args.proof_type, Some(ProofType::Compressed));
+    }
+
+    #[test]
+    fn network_profile_sets_the_urls_not_given() {
+        let config = config(&format!(
+            r#"
+network = "local"
+
+[networks.local]
+rpc-url = "http://localhost:8545"
+batcher-url = "ws://localhost:8080"
+{SERVICES}"#
+        ));
+        let (mut args, matches) = parse(&["--batcher-url", "ws://flag:8080"]);
+        config.apply(&mut args, &matches).unwrap();
+        assert_eq!(args.aligned.network, "local");
+        assert_eq!(args.aligned.rpc_url, "http://localhost:8545");
+        assert_eq!(args.aligned.batcher_url, "ws://flag:8080");
+    }
+
+    #[test]
+    fn invalid_config_values_are_rejected() {
+        let (mut args, matches) = parse(&[]);
+        assert!(config(r#"proof-type = "groth16""#)
+            .apply(&mut args, &matches)
+            .is_err());
+        let mut backend = None;
+        assert!(config(r#"backend = "jolt""#)
+            .apply_backend(&mut backend)
+            .is_err());
+        assert!(toml::from_str::<Config>("proof-data-dir = \"proofs\"").is_err());
+    }
+
+    #[test]
+    fn relative_paths_are_resolved_from_the_config_file() {
+        let (home, project) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(
+            home.path().join(CONFIG_FILE),
+            "backend = \"risc0\"\nkeystore-path = \"keystore.json\"\n",
+        )
+        .unwrap();
+        fs::write(
+            project.path().join(CONFIG_FILE),
+            "input = \"inputs/a.json\"\nproof-data-path = \"proofs\"\n",
+        )
+        .unwrap();
+        let config = Config::load(home.path(), project.path()).unwrap();
+        assert_eq!(
+            config.keystore_path,
+            Some(home.path().join("keystore.json"))
+        );
+        assert_eq!(
+            config.input,
+            Some(project.path().join("inputs/a.json").display().to_string())
+        );
+        assert_eq!(
+            config.proof_data_path,
+            Some(project.path().join("proofs").display().to_string())
+        );
+        let mut backend = None;
+        config.apply_backend(&mut backend).unwrap();
+        assert_eq!(backend, Some(Backend::Risc0));
+    }
+}
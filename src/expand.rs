@@ -0,0 +1,129 @@
+use clap::Args;
+use log::info;
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{input::HostInput, utils, Backend};
+
+#[derive(Args, Debug)]
+pub struct ExpandArgs {
+    pub guest_path: String,
+    #[clap(
+        long = "backend",
+        value_enum,
+        help = "zkVM the program is generated for"
+    )]
+    pub backend: Backend,
+}
+
+#[derive(Args, Debug)]
+pub struct EjectArgs {
+    pub guest_path: String,
+    #[clap(
+        long = "backend",
+        value_enum,
+        help = "zkVM the program is generated for"
+    )]
+    pub backend: Backend,
+    #[clap(
+        long = "out",
+        help = "Directory the standalone workspace is written to"
+    )]
+    pub out_dir: PathBuf,
+}
+
+/// File generated by zkRust in the backend workspace
+#[derive(Debug, Serialize)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Result of the `expand` command.
+#[derive(Debug, Serialize)]
+pub struct ExpandOutput {
+    pub backend: Backend,
+    pub guest: GeneratedFile,
+    pub host: GeneratedFile,
+}
+
+/// Result of the `eject` command.
+#[derive(Debug, Serialize)]
+pub struct EjectOutput {
+    pub backend: Backend,
+    pub path: PathBuf,
+}
+
+/// Generates the guest and host programs of `guest_path` for `backend` and returns them.
+pub fn expand(guest_path: &Path, backend: Backend, home_dir: &Path) -> io::Result<ExpandOutput> {
+    validate(guest_path)?;
+    backend.prepare(guest_path, home_dir, HostInput::Function)?;
+    let guest = read_generated(&home_dir.join(backend.guest_main()));
+    let host = read_generated(&home_dir.join(backend.host_main()));
+    backend.clear_host(home_dir)?;
+
+    Ok(ExpandOutput {
+        backend,
+        guest: guest?,
+        host: host?,
+    })
+}
+
+/// Writes the complete backend workspace generated for `guest_path` into `out_dir`,
+/// so it can be built and modified without zkRust.
+pub fn eject(
+    guest_path: &Path,
+    backend: Backend,
+    home_dir: &Path,
+    out_dir: &Path,
+) -> io::Result<EjectOutput> {
+    validate(guest_path)?;
+    if out_dir.exists() && fs::read_dir(out_dir)?.next().is_some() {
+        return Err(io::Error::other(format!(
+            "Destination {:?} already exists and is not empty",
+            out_dir
+        )));
+    }
+
+    backend.prepare(guest_path, home_dir, HostInput::Function)?;
+    let copied = utils::copy_dir_all_except(
+        &home_dir.join(backend.workspace_dir()),
+        out_dir,
+        &["target"],
+    );
+    backend.clear_host(home_dir)?;
+    copied?;
+
+    let run_dir = match backend {
+        Backend::Sp1 => out_dir.join("script"),
+        Backend::Risc0 => out_dir.to_path_buf(),
+    };
+    info!(
+        "Run `cargo run --release -- <PROOF_DATA_PARENT_DIR>` in {:?} to generate a proof",
+        run_dir
+    );
+
+    Ok(EjectOutput {
+        backend,
+        path: out_dir.to_path_buf(),
+    })
+}
+
+fn validate(guest_path: &Path) -> io::Result<()> {
+    if !utils::validate_directory_structure(&guest_path.to_string_lossy()) {
+        return Err(io::Error::other(
+            "zkRust directory structure invalid please consult the README",
+        ));
+    }
+    Ok(())
+}
+
+fn read_generated(path: &Path) -> io::Result<GeneratedFile> {
+    Ok(GeneratedFile {
+        path: path.to_path_buf(),
+        contents: fs::read_to_string(path)?,
+    })
+}
@@ -0,0 +1,479 @@
+use clap::ValueEnum;
+use log::info;
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::utils;
+
+/// Environment variable holding the path of the input file read by the host.
+pub const INPUT_ENV: &str = "ZKRUST_INPUT";
+/// Environment variable holding the format of the input file read by the host.
+pub const INPUT_FORMAT_ENV: &str = "ZKRUST_INPUT_FORMAT";
+pub const BASE_INPUT_LOADER: &str = "workspaces/base_files/input_loader";
+// Inputs piped through stdin are saved here so the host can read them like any input file.
+const STDIN_INPUT_FILE: &str = "stdin_input";
+
+pub const SERDE_JSON_DEPENDENCY: &str = "serde_json = \"1.0\"";
+pub const SERDE_CBOR_DEPENDENCY: &str = "serde_cbor = \"0.11\"";
+
+/// Encoding of the values in an input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// A JSON array
+    Json,
+    /// Values encoded with bincode one after the other
+    Bincode,
+    /// A CBOR array
+    Cbor,
+}
+
+impl InputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InputFormat::Json => "json",
+            InputFormat::Bincode => "bincode",
+            InputFormat::Cbor => "cbor",
+        }
+    }
+
+    // Files without a known extension are read as JSON.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("bin") | Some("bincode") => InputFormat::Bincode,
+            Some("cbor") => InputFormat::Cbor,
+            _ => InputFormat::Json,
+        }
+    }
+}
+
+/// Where the host gets the values written to the guest from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HostInput {
+    /// The body of the `input()` function of the program
+    #[default]
+    Function,
+    /// The input file passed with `--input`, read when the host runs
+    File,
+}
+
+/// Value read by the guest, in the order of the reads in `main()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuestRead {
+    /// `let x: T = zk_rust_io::read();`
+    Value(String),
+    /// `zk_rust_io::read_slice();`
+    Slice,
+}
+
+/// Input file passed to a `prove-*` command.
+#[derive(Debug)]
+pub struct InputFile {
+    pub path: PathBuf,
+    pub format: InputFormat,
+}
+
+impl InputFile {
+    /// Resolves `--input` to an absolute path, `-` reads the input from stdin.
+    pub fn resolve(
+        input: &str,
+        format: Option<InputFormat>,
+        home_dir: &Path,
+    ) -> io::Result<InputFile> {
+        if input == "-" {
+            let mut bytes = vec![];
+            io::stdin().read_to_end(&mut bytes)?;
+            let path = home_dir.join(STDIN_INPUT_FILE);
+            fs::write(&path, bytes)?;
+            return Ok(InputFile {
+                path,
+                format: format.unwrap_or(InputFormat::Json),
+            });
+        }
+        let path = PathBuf::from(input)
+            .canonicalize()
+            .map_err(|e| io::Error::other(format!("Failed to open input file {}: {}", input, e)))?;
+        let format = format.unwrap_or_else(|| InputFormat::from_path(&path));
+        Ok(InputFile { path, format })
+    }
+
+    /// Checks the input file against the reads of the guest before anything is built. JSON values
+    /// are checked against the types of the reads, the types defined by the program are only
+    /// checked by the host before proving.
+    pub fn validate(&self, guest_path: &Path) -> io::Result<()> {
+        let reads = guest_reads(&main_body(guest_path)?)?;
+        info!(
+            "Reading {} input(s) from {}",
+            reads.len(),
+            self.path.display()
+        );
+        // Only JSON is checked here, bincode is not self describing
+        if self.format != InputFormat::Json {
+            return Ok(());
+        }
+        let contents = fs::read(&self.path)?;
+        let values: Vec<serde_json::Value> = serde_json::from_slice(&contents).map_err(|e| {
+            io::Error::other(format!(
+                "JSON input must be an array with one value per read: {}",
+                e
+            ))
+        })?;
+        if values.len() != reads.len() {
+            return Err(io::Error::other(format!(
+                "The program reads {} input(s) but {} has {} value(s)",
+                reads.len(),
+                self.path.display(),
+                values.len()
+            )));
+        }
+        for (index, (read, value)) in reads.iter().zip(&values).enumerate() {
+            let ty = match read {
+                GuestRead::Value(ty) => ty.as_str(),
+                GuestRead::Slice => "Vec<u8>",
+            };
+            check_json_value(ty, value).map_err(|e| {
+                io::Error::other(format!(
+                    "Input {} of {} is not a valid `{}`: {}",
+                    index,
+                    self.path.display(),
+                    ty,
+                    e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Environment of the host program telling it which file to read.
+    pub fn envs(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (INPUT_ENV, self.path.display().to_string()),
+            (INPUT_FORMAT_ENV, self.format.as_str().to_string()),
+        ]
+    }
+}
+
+/// Reads of `zk_rust_io::read()` and `zk_rust_io::read_slice()` in `main_body`, in order.
+/// Every `read()` must annotate the type of its value, the host deserializes the input file into it.
+/// Reads in loops are rejected, the number of values they read is only known when the guest runs.
+pub fn guest_reads(main_body: &str) -> io::Result<Vec<GuestRead>> {
+    let read_regex = Regex::new(r"zk_rust_io::read(_slice)?\(\)").map_err(io::Error::other)?;
+    let let_regex =
+        Regex::new(r"^\s*let\s+(mut\s+)?[^:=]+:\s*(.+?)\s*=\s*$").map_err(io::Error::other)?;
+    let loop_regex =
+        Regex::new(r"(?s)(^|\W)(for\s.*\sin\s|while\s|loop\s*$)").map_err(io::Error::other)?;
+
+    let mut reads = vec![];
+    for capture in read_regex.captures_iter(main_body) {
+        let read_start = capture.get(0).map_or(0, |read| read.start());
+        if in_loop(main_body, read_start, &loop_regex) {
+            return Err(io::Error::other(format!(
+                "`{}` in a loop reads a number of values only known when the program runs, read a `Vec` of the values instead",
+                &capture[0]
+            )));
+        }
+        if capture.get(1).is_some() {
+            reads.push(GuestRead::Slice);
+            continue;
+        }
+        let statement_start = main_body[..read_start]
+            .rfind([';', '{', '}'])
+            .map_or(0, |index| index + 1);
+        let statement = &main_body[statement_start..read_start];
+        let Some(let_capture) = let_regex.captures(statement) else {
+            return Err(io::Error::other(format!(
+                "`{}zk_rust_io::read()` needs a type to read it from an input file, write `let value: Type = zk_rust_io::read();`",
+                statement.trim_start()
+            )));
+        };
+        reads.push(GuestRead::Value(let_capture[2].to_string()));
+    }
+    Ok(reads)
+}
+
+// Whether `index` is in the body of a `for`, `while` or `loop` of `main_body`.
+fn in_loop(main_body: &str, index: usize, loop_regex: &Regex) -> bool {
+    // One entry per open block, whether it is the body of a loop
+    let mut blocks = vec![];
+    let mut header_start = 0;
+    for (position, character) in main_body[..index].char_indices() {
+        match character {
+            '{' => {
+                blocks.push(loop_regex.is_match(&main_body[header_start..position]));
+                header_start = position + 1;
+            }
+            '}' => {
+                blocks.pop();
+                header_start = position + 1;
+            }
+            ';' => header_start = position + 1,
+            _ => {}
+        }
+    }
+    blocks.contains(&true)
+}
+
+// Checks a JSON value against the type it is deserialized into, as serde_json would. Types whose
+// definition zkRust does not see, like the structs of the program, are accepted.
+fn check_json_value(ty: &str, value: &serde_json::Value) -> Result<(), String> {
+    let ty = ty.trim().trim_start_matches('&').trim();
+    let expected = |kind: &str| Err(format!("expected {}, found {}", kind, value));
+
+    // Tuples and the unit type
+    if let Some(elements) = ty.strip_prefix('(').and_then(|ty| ty.strip_suffix(')')) {
+        let element_types = split_type_list(elements);
+        if element_types.is_empty() {
+            return if value.is_null() {
+                Ok(())
+            } else {
+                expected("null")
+            };
+        }
+        let Some(values) = value
+            .as_array()
+            .filter(|values| values.len() == element_types.len())
+        else {
+            return expected(&format!("an array of {} values", element_types.len()));
+        };
+        return element_types
+            .iter()
+            .zip(values)
+            .try_for_each(|(ty, value)| check_json_value(ty, value));
+    }
+    // Arrays `[T; N]`
+    if let Some((element_type, length)) = ty
+        .strip_prefix('[')
+        .and_then(|ty| ty.strip_suffix(']'))
+        .and_then(|ty| ty.rsplit_once(';'))
+    {
+        // Lengths given by constants or expressions are left to the host
+        let length: Option<usize> = length.trim().parse().ok();
+        let Some(values) = value
+            .as_array()
+            .filter(|values| length.map_or(true, |length| values.len() == length))
+        else {
+            return match length {
+                Some(length) => expected(&format!("an array of {} values", length)),
+                None => expected("an array"),
+            };
+        };
+        return values
+            .iter()
+            .try_for_each(|value| check_json_value(element_type, value));
+    }
+
+    let (path, arguments) = match ty.split_once('<') {
+        Some((path, arguments)) => (
+            path.trim(),
+            split_type_list(arguments.strip_suffix('>').unwrap_or(arguments)),
+        ),
+        None => (ty, vec![]),
+    };
+    let name = path.rsplit("::").next().unwrap_or(path);
+    match (name, arguments.as_slice()) {
+        ("bool", []) => deserializes::<bool>(value, "a boolean"),
+        ("String" | "str", []) => deserializes::<String>(value, "a string"),
+        ("char", []) => deserializes::<char>(value, "a single character string"),
+        ("f32", []) => deserializes::<f32>(value, "a number"),
+        ("f64", []) => deserializes::<f64>(value, "a number"),
+        ("u8", []) => deserializes::<u8>(value, "a u8"),
+        ("u16", []) => deserializes::<u16>(value, "a u16"),
+        ("u32", []) => deserializes::<u32>(value, "a u32"),
+        ("u64", []) => deserializes::<u64>(value, "a u64"),
+        ("u128", []) => deserializes::<u128>(value, "a u128"),
+        ("usize", []) => deserializes::<usize>(value, "a usize"),
+        ("i8", []) => deserializes::<i8>(value, "an i8"),
+        ("i16", []) => deserializes::<i16>(value, "an i16"),
+        ("i32", []) => deserializes::<i32>(value, "an i32"),
+        ("i64", []) => deserializes::<i64>(value, "an i64"),
+        ("i128", []) => deserializes::<i128>(value, "an i128"),
+        ("isize", []) => deserializes::<isize>(value, "an isize"),
+        ("Option", [element_type]) => match value.is_null() {
+            true => Ok(()),
+            false => check_json_value(element_type, value),
+        },
+        ("Box", [element_type]) => check_json_value(element_type, value),
+        ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [element_type]) => match value.as_array() {
+            Some(values) => values
+                .iter()
+                .try_for_each(|value| check_json_value(element_type, value)),
+            None => expected("an array"),
+        },
+        ("HashMap" | "BTreeMap", [_, value_type]) => match value.as_object() {
+            Some(entries) => entries
+                .values()
+                .try_for_each(|value| check_json_value(value_type, value)),
+            None => expected("an object"),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn deserializes<T: DeserializeOwned>(value: &serde_json::Value, kind: &str) -> Result<(), String> {
+    match T::deserialize(value) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("expected {}, found {}", kind, value)),
+    }
+}
+
+// Splits `A, B<C, D>, (E, F)` at its top level commas.
+fn split_type_list(types: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (position, character) in types.char_indices() {
+        match character {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&types[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&types[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Host code writing the values of the input file to the guest, in place of the `input()` body.
+pub fn host_input_code(reads: &[GuestRead]) -> String {
+    let mut code = vec!["let mut zkrust_input = ZkRustInput::load(&zkrust_run.input);".to_string()];
+    for (index, read) in reads.iter().enumerate() {
+        code.push(match read {
+            GuestRead::Value(ty) => format!(
+                "stdin.write(&zkrust_input.next::<{}>({}, {:?}));",
+                ty, index, ty
+            ),
+            GuestRead::Slice => format!(
+                "stdin.write_slice(&zkrust_input.next::<Vec<u8>>({}, \"Vec<u8>\"));",
+                index
+            ),
+        });
+    }
+    code.push(format!("zkrust_input.finish({});", reads.len()));
+    code.join("\n        ")
+}
+
+/// Appends the input loader to the generated host and adds the crates it deserializes with.
+pub fn add_input_loader(
+    home_dir: &Path,
+    host_main: &Path,
+    host_cargo_toml: &Path,
+) -> io::Result<()> {
+    let mut host = OpenOptions::new().append(true).open(host_main)?;
+    host.write_all(fs::read_to_string(home_dir.join(BASE_INPUT_LOADER))?.as_bytes())?;
+    for (name, dependency) in [
+        ("serde", utils::SERDE_DEPENDENCY),
+        ("serde_json", SERDE_JSON_DEPENDENCY),
+        ("serde_cbor", SERDE_CBOR_DEPENDENCY),
+        ("bincode", utils::BINCODE_DEPENDENCY),
+    ] {
+        utils::add_dependency(host_cargo_toml, name, dependency)?;
+    }
+    Ok(())
+}
+
+fn main_body(guest_path: &Path) -> io::Result<String> {
+    let Ok(function_bodies) = utils::extract_function_bodies(
+        &guest_path.join("src/main.rs"),
+        vec!["fn main()".to_string()],
+    ) else {
+        return Err(io::Error::other("Failed to extract function bodies"));
+    };
+    Ok(function_bodies.into_iter().next().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn guest_reads_types_and_slices_in_order() {
+        let main_body = "
+    let n: u32 = zk_rust_io::read();
+    let mut pairs: Vec<(u8, bool)> = zk_rust_io::read();
+    let bytes = zk_rust_io::read_slice();
+    if n > 1 {
+        let name: String = zk_rust_io::read();
+    }
+";
+        assert_eq!(
+            guest_reads(main_body).unwrap(),
+            vec![
+                GuestRead::Value("u32".to_string()),
+                GuestRead::Value("Vec<(u8, bool)>".to_string()),
+                GuestRead::Slice,
+                GuestRead::Value("String".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn guest_reads_need_a_type() {
+        let error = guest_reads("\n    let n = zk_rust_io::read();\n").unwrap_err();
+        assert!(error.to_string().contains("needs a type"));
+    }
+
+    #[test]
+    fn guest_reads_reject_reads_in_loops() {
+        for main_body in [
+            "for _ in 0..3 { let n: u32 = zk_rust_io::read(); }",
+            "let mut i = 0; while i < 3 { let n: u32 = zk_rust_io::read(); i += 1; }",
+            "loop { if true { let bytes = zk_rust_io::read_slice(); } break; }",
+        ] {
+            let error = guest_reads(main_body).unwrap_err();
+            assert!(error.to_string().contains("in a loop"), "{}", main_body);
+        }
+        // Reads after a loop are fine
+        assert_eq!(
+            guest_reads("for i in 0..3 { println!(\"{}\", i); } let n: u32 = zk_rust_io::read();")
+                .unwrap(),
+            vec![GuestRead::Value("u32".to_string())]
+        );
+    }
+
+    #[test]
+    fn host_input_code_writes_every_read() {
+        let reads = [GuestRead::Value("Vec<u32>".to_string()), GuestRead::Slice];
+        assert_eq!(
+            host_input_code(&reads),
+            "let mut zkrust_input = ZkRustInput::load(&zkrust_run.input);
+        stdin.write(&zkrust_input.next::<Vec<u32>>(0, \"Vec<u32>\"));
+        stdin.write_slice(&zkrust_input.next::<Vec<u8>>(1, \"Vec<u8>\"));
+        zkrust_input.finish(2);"
+        );
+    }
+
+    #[test]
+    fn check_json_value_of_known_types() {
+        assert!(check_json_value("u8", &json!(255)).is_ok());
+        assert!(check_json_value("u8", &json!(256)).is_err());
+        assert!(check_json_value("i32", &json!(-3)).is_ok());
+        assert!(check_json_value("u32", &json!("3")).is_err());
+        assert!(check_json_value("Vec<u8>", &json!([1, 2, 3])).is_ok());
+        assert!(check_json_value("Vec<u8>", &json!([1, -2])).is_err());
+        assert!(check_json_value("(u32, bool)", &json!([1, true])).is_ok());
+        assert!(check_json_value("(u32, bool)", &json!([1])).is_err());
+        assert!(check_json_value("[u8; 2]", &json!([1, 2])).is_ok());
+        assert!(check_json_value("[u8; 2]", &json!([1, 2, 3])).is_err());
+        assert!(check_json_value("[u8; SIZE]", &json!([1, 2, 3])).is_ok());
+        assert!(check_json_value("[u8; 32 * 2]", &json!([1, 256])).is_err());
+        assert!(check_json_value("[u8; SIZE]", &json!(1)).is_err());
+        assert!(check_json_value("Option<String>", &json!(null)).is_ok());
+        assert!(
+            check_json_value("std::collections::HashMap<String, u64>", &json!({"a": 1})).is_ok()
+        );
+        assert!(check_json_value("HashMap<String, u64>", &json!({"a": true})).is_err());
+        // Types of the program are left to the host
+        assert!(check_json_value("Header", &json!("anything")).is_ok());
+    }
+}
@@ -1,26 +1,51 @@
 use aligned_sdk::core::errors::{AlignedError, SubmitError};
 use ethers::utils::format_units;
-use log::{error, info};
+use input::{HostInput, InputFormat};
+use log::{error, info, warn};
+use network::{AlignedNetwork, NetworkProfile};
+use output::OutputFormat;
+use precompiles::PatchedManifest;
+use record::SubmissionRecord;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use signer::SignerSource;
+use source_map::SourceMaps;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
-use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use aligned_sdk::core::types::{
     AlignedVerificationData, Network, PriceEstimate, ProvingSystemId, VerificationData,
 };
-use aligned_sdk::sdk::{
-    deposit_to_aligned, estimate_fee, get_balance_in_aligned, get_chain_id, get_next_nonce,
-    submit_and_wait_verification,
-};
+use aligned_sdk::sdk::{estimate_fee, get_chain_id};
 use clap::{Args, ValueEnum};
 use dialoguer::Confirm;
 use ethers::prelude::*;
 use ethers::providers::Http;
 use ethers::signers::LocalWallet;
 
+pub mod aligned;
+pub mod batch;
+pub mod cache;
+pub mod config;
+pub mod expand;
+pub mod input;
+pub mod lint;
+pub mod manifest;
+pub mod mock_batcher;
+pub mod network;
+pub mod output;
+pub mod precompiles;
+pub mod profile;
+pub mod record;
 pub mod risc0;
+pub mod scaffold;
+pub mod signer;
+pub mod source_map;
 pub mod sp1;
+pub mod submit;
 pub mod utils;
 
 // Make proof_data path optional
@@ -30,11 +55,59 @@ pub struct ProofArgs {
     pub guest_path: String,
     #[clap(long = "submit-to-aligned")]
     pub submit_to_aligned: bool,
+    #[command(flatten)]
+    pub aligned: SubmissionArgs,
+    #[clap(
+        name = "Enables zkVM Acceleration via VM Precompiles",
+        long = "precompiles"
+    )]
+    pub precompiles: bool,
+    #[arg(
+        name = "Proof data directory path",
+        long = "proof-data-path",
+        default_value = "./proof_data"
+    )]
+    pub proof_data_directory_path: String,
+    #[clap(
+        long = "proof-type",
+        value_enum,
+        help = "Proof generated, defaults to `compressed` with SP1 and `core` with RISC0"
+    )]
+    pub proof_type: Option<ProofType>,
+    #[clap(
+        long = "input",
+        help = "File with the values read by the program, used instead of `input()`. `-` reads them from stdin"
+    )]
+    pub input: Option<String>,
+    #[clap(
+        long = "input-format",
+        value_enum,
+        help = "Encoding of the input file, guessed from its extension when omitted"
+    )]
+    pub input_format: Option<InputFormat>,
     #[clap(
-        name = "Path to Wallet Key Store",
-        long = "keystore-path",
-        required_if_eq("submit_to_aligned", "true")
+        last = true,
+        help = "Arguments passed to `input()` and `output()`, read with `zk_rust_io::host_args()`"
     )]
+    pub host_args: Vec<String>,
+}
+
+/// Wallet and Aligned network proofs are submitted with.
+#[derive(Args, Debug)]
+pub struct SubmissionArgs {
+    #[clap(
+        long = "signer",
+        default_value = "keystore",
+        help = "Key of the wallet paying for the proofs: keystore, private-key-env:<VAR>, private-key-file:<PATH>, mnemonic-env:<VAR>, mnemonic-file:<PATH> or private-key-command:<COMMAND> (a command exporting the private key)"
+    )]
+    pub signer: SignerSource,
+    #[clap(
+        long = "derivation-path",
+        default_value = signer::DEFAULT_DERIVATION_PATH,
+        help = "Account derived from the mnemonic of a mnemonic-* signer"
+    )]
+    pub derivation_path: String,
+    #[clap(name = "Path to Wallet Key Store", long = "keystore-path")]
     pub keystore_path: Option<PathBuf>,
     #[clap(
         name = "URL of an Ethereum RPC Node",
@@ -45,43 +118,229 @@ pub struct ProofArgs {
     #[clap(
         name = "The working network's name",
         long = "network",
-        default_value = "holesky"
+        default_value = "holesky",
+        help = "devnet, holesky, holesky-stage or a `[networks.<name>]` profile of zkrust.toml"
     )]
-    pub network: NetworkArg,
+    pub network: String,
     #[clap(
         name = "Payment send to the BatcherServicContract to fund Proof submission (Wei)",
         long = "batcher-payment",
         default_value("4000000000000000")
     )]
     pub batcher_payment: u128,
-    #[clap(
-        name = "Enables zkVM Acceleration via VM Precompiles",
-        long = "precompiles"
-    )]
-    pub precompiles: bool,
     #[arg(
         name = "Aligned verification data directory Path",
         long = "aligned-verification-data-path",
         default_value = "./aligned_verification_data/"
     )]
     pub batch_inclusion_data_directory_path: String,
-    #[arg(
-        name = "Proof data directory path",
-        long = "proof-data-path",
-        default_value = "./proof_data"
-    )]
-    pub proof_data_directory_path: String,
     #[clap(
         name = "URL of the Aligned Batcher",
         long = "batcher-url",
         default_value("wss://batcher.alignedlayer.com")
     )]
     pub batcher_url: String,
+    #[clap(
+        long = "retries",
+        default_value_t = 3,
+        help = "Times the proofs are sent again after the connection to the batcher failed"
+    )]
+    pub retries: u32,
+    #[clap(
+        long = "submit-timeout",
+        default_value_t = 600,
+        help = "Seconds to wait for the batcher to answer with the batch of the proofs"
+    )]
+    pub submit_timeout: u64,
+    #[clap(
+        long = "verification-timeout",
+        default_value_t = 600,
+        help = "Seconds to wait for the batch of the proofs to be verified"
+    )]
+    pub verification_timeout: u64,
+    #[clap(
+        long = "yes",
+        help = "Pay the deposit and the fees of the proofs without asking, e.g. in scripts"
+    )]
+    pub assume_yes: bool,
+    /// `[networks.<name>]` profiles of zkrust.toml, `--network` may name one of them
+    #[clap(skip)]
+    pub network_profiles: BTreeMap<String, NetworkProfile>,
 }
 
 const MIN_FEE_PER_PROOF: u128 = 13_000 * 100_000_000; // gas_price = 0.1 Gwei = 0.0000000001 ether (low gas price)
+const SUBMIT_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Outcome of a proof verified on Aligned
+#[derive(Debug, Serialize)]
+pub struct AlignedSubmission {
+    pub batch_merkle_root: String,
+    /// Only known for networks with an explorer
+    pub explorer_url: Option<String>,
+    pub verification_data_path: PathBuf,
+    /// Submission record of the proof, read by `zkRust aligned status`
+    pub record: PathBuf,
+}
+
+/// zkVM used to prove a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Sp1,
+    Risc0,
+}
+
+impl Backend {
+    /// Copies the user program into the backend workspace and generates its guest and host
+    pub fn prepare(
+        &self,
+        guest_path: &Path,
+        home_dir: &Path,
+        host_input: HostInput,
+    ) -> std::io::Result<SourceMaps> {
+        match self {
+            Backend::Sp1 => sp1::prepare(guest_path, home_dir, host_input),
+            Backend::Risc0 => risc0::prepare(guest_path, home_dir, host_input),
+        }
+    }
+
+    /// Name of the backend, also used for its `proof_data` subdirectory and artifact files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Sp1 => "sp1",
+            Backend::Risc0 => "risc0",
+        }
+    }
+
+    /// Proving system the batcher verifies the proofs of the backend with
+    pub fn proving_system(&self) -> ProvingSystemId {
+        match self {
+            Backend::Sp1 => ProvingSystemId::SP1,
+            Backend::Risc0 => ProvingSystemId::Risc0,
+        }
+    }
+
+    /// Builds the generated workspace and runs its host, which writes the proofs.
+    pub fn generate_proof(
+        &self,
+        home_dir: &Path,
+        current_dir: &PathBuf,
+        stdout: std::process::Stdio,
+        envs: &[(&str, String)],
+        host_args: &[String],
+    ) -> std::io::Result<std::process::ExitStatus> {
+        match self {
+            Backend::Sp1 => sp1::generate_sp1_proof(
+                &home_dir.join(sp1::SP1_SCRIPT_DIR),
+                current_dir,
+                stdout,
+                envs,
+                host_args,
+            ),
+            Backend::Risc0 => risc0::generate_risc0_proof(
+                &home_dir.join(risc0::RISC0_WORKSPACE_DIR),
+                current_dir,
+                stdout,
+                envs,
+                host_args,
+            ),
+        }
+    }
+
+    /// Patches the guest manifest with the precompiles of the backend until the result is dropped
+    pub fn apply_precompiles(
+        &self,
+        guest_path: &Path,
+        home_dir: &Path,
+    ) -> std::io::Result<PatchedManifest> {
+        PatchedManifest::apply(*self, guest_path, home_dir)
+    }
+
+    /// Builds the generated workspace and prints the diagnostics of the build mapped back to the
+    /// user project. Returns the number of compile errors.
+    pub fn build(
+        &self,
+        home_dir: &Path,
+        envs: &[(&str, String)],
+        source_maps: &SourceMaps,
+        output_format: OutputFormat,
+    ) -> std::io::Result<usize> {
+        let (host_dir, guest_dir, toolchain) = match self {
+            Backend::Sp1 => (
+                sp1::SP1_SCRIPT_DIR,
+                sp1::SP1_SRC_DIR,
+                (sp1::SP1_GUEST_TOOLCHAIN, sp1::SP1_GUEST_TARGET),
+            ),
+            Backend::Risc0 => (
+                risc0::RISC0_WORKSPACE_DIR,
+                risc0::RISC0_SRC_DIR,
+                (risc0::RISC0_GUEST_TOOLCHAIN, risc0::RISC0_GUEST_TARGET),
+            ),
+        };
+        source_map::build_workspace(
+            &home_dir.join(host_dir),
+            envs,
+            &home_dir.join(guest_dir),
+            toolchain,
+            source_maps,
+            output_format,
+        )
+    }
+
+    /// Workspace directory relative to the zkRust home directory
+    pub fn workspace_dir(&self) -> &'static str {
+        match self {
+            Backend::Sp1 => sp1::SP1_WORKSPACE_DIR,
+            Backend::Risc0 => risc0::RISC0_WORKSPACE_DIR,
+        }
+    }
+
+    pub fn guest_main(&self) -> &'static str {
+        match self {
+            Backend::Sp1 => sp1::SP1_GUEST_MAIN,
+            Backend::Risc0 => risc0::RISC0_GUEST_MAIN,
+        }
+    }
+
+    pub fn host_main(&self) -> &'static str {
+        match self {
+            Backend::Sp1 => sp1::SP1_HOST_MAIN,
+            Backend::Risc0 => risc0::RISC0_HOST_MAIN,
+        }
+    }
+
+    /// Restores the host program of the workspace to its base file
+    pub fn clear_host(&self, home_dir: &Path) -> std::io::Result<()> {
+        let base_host = match self {
+            Backend::Sp1 => sp1::SP1_BASE_HOST_FILE,
+            Backend::Risc0 => risc0::RISC0_BASE_HOST_FILE,
+        };
+        std::fs::copy(home_dir.join(base_host), home_dir.join(self.host_main()))?;
+        Ok(())
+    }
+}
 
-#[derive(Debug, Clone, ValueEnum, Copy)]
+/// Proof generated by a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofType {
+    /// Proofs of the shards (SP1) or segments (RISC0) of the execution, the fastest to generate
+    Core,
+    /// Core proofs recursively compressed into one, a SP1 compressed proof or a RISC0 succinct receipt
+    Compressed,
+}
+
+impl ProofType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProofType::Core => "core",
+            ProofType::Compressed => "compressed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum NetworkArg {
     Devnet,
     Holesky,
@@ -98,38 +357,83 @@ impl From<NetworkArg> for Network {
     }
 }
 
-pub async fn submit_proof_to_aligned(
-    proof_path: &str,
-    elf_path: &str,
-    pub_input_path: Option<&str>,
-    args: &ProofArgs,
-    proof_system_id: ProvingSystemId,
-) -> Result<(), AlignedError> {
-    let keystore_password = rpassword::prompt_password("Enter keystore password: ")
-        .map_err(|e| AlignedError::SubmitError(SubmitError::WalletSignerError(e.to_string())))?;
+/// Proof read from the files written by a host, as submitted to Aligned.
+#[derive(Debug)]
+pub struct AlignedProof {
+    /// Proof file, its submission record is written next to it
+    pub path: PathBuf,
+    pub proving_system: ProvingSystemId,
+    pub proof: Vec<u8>,
+    /// SP1 ELF or RISC0 image ID
+    pub program: Vec<u8>,
+    /// Public inputs are optional.
+    pub pub_input: Option<Vec<u8>>,
+}
 
-    let network: Network = args.network.into();
-    let Some(keystore_path) = args.keystore_path.clone() else {
-        return Err(SubmitError::GenericError(
-            "Keystore path no found. Please supply path to your local wallet keystore.".to_string(),
-        ))?;
-    };
-    let local_wallet = LocalWallet::decrypt_keystore(&keystore_path, keystore_password)
-        .map_err(|e| AlignedError::SubmitError(SubmitError::WalletSignerError(e.to_string())))?;
-    let chain_id = get_chain_id(&args.rpc_url).await?;
-    let wallet = local_wallet.with_chain_id(chain_id);
+impl AlignedProof {
+    pub fn read(
+        proof_path: &Path,
+        program_path: &Path,
+        pub_input_path: Option<&Path>,
+        proving_system: ProvingSystemId,
+    ) -> Result<Self, SubmitError> {
+        let read = |path: &Path| {
+            std::fs::read(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))
+        };
+        Ok(AlignedProof {
+            path: proof_path.to_path_buf(),
+            proving_system,
+            proof: read(proof_path)?,
+            program: read(program_path)?,
+            pub_input: pub_input_path.map(read).transpose()?,
+        })
+    }
+}
 
-    let proof = std::fs::read(proof_path)
-        .map_err(|e| AlignedError::SubmitError(SubmitError::GenericError(e.to_string())))?;
+pub async fn submit_proof_to_aligned(
+    proof_path: &Path,
+    elf_path: &Path,
+    pub_input_path: Option<&Path>,
+    args: &SubmissionArgs,
+    proof_system_id: ProvingSystemId,
+) -> Result<AlignedSubmission, AlignedError> {
+    let proof = AlignedProof::read(proof_path, elf_path, pub_input_path, proof_system_id)?;
+    let mut submissions = submit_proofs_to_aligned(vec![proof], args).await?;
+    Ok(submissions.remove(0))
+}
 
-    let elf_data = std::fs::read(elf_path)
-        .map_err(|e| AlignedError::SubmitError(SubmitError::GenericError(e.to_string())))?;
+/// Submits `proofs` in one call to the batcher, paid with a single deposit and confirmation,
+/// and waits for them to be verified. Returns the submission of each proof, in order.
+pub async fn submit_proofs_to_aligned(
+    proofs: Vec<AlignedProof>,
+    args: &SubmissionArgs,
+) -> Result<Vec<AlignedSubmission>, AlignedError> {
+    let submissions = batch_proofs_on_aligned(proofs, args).await?;
+    let records: Vec<PathBuf> = submissions
+        .iter()
+        .map(|submission| submission.record.clone())
+        .collect();
+    wait_for_verification(&records, args).await?;
+    Ok(submissions)
+}
 
-    // Public inputs are optional.
-    let pub_input = pub_input_path
-        .map(std::fs::read)
-        .transpose()
+/// Submits `proofs` in one call to the batcher and returns once it answered with their batch
+/// inclusion, saved to the record of each proof. Returns the submission of each proof, in order.
+pub async fn batch_proofs_on_aligned(
+    proofs: Vec<AlignedProof>,
+    args: &SubmissionArgs,
+) -> Result<Vec<AlignedSubmission>, AlignedError> {
+    if proofs.is_empty() {
+        return Err(SubmitError::GenericError("No proofs to submit".to_string()))?;
+    }
+    let network = network::resolve(&args.network, &args.network_profiles)
         .map_err(|e| SubmitError::GenericError(e.to_string()))?;
+    let local_wallet = args
+        .signer
+        .wallet(args.keystore_path.as_deref(), &args.derivation_path)?;
+    let chain_id = get_chain_id(&args.rpc_url).await?;
+    network.check_chain_id(chain_id)?;
+    let wallet = local_wallet.with_chain_id(chain_id);
 
     let provider = Provider::<Http>::try_from(&args.rpc_url)
         .map_err(|e| SubmitError::EthereumProviderError(e.to_string()))?;
@@ -143,16 +447,13 @@ pub async fn submit_proof_to_aligned(
     if max_fee < U256::from(MIN_FEE_PER_PROOF) {
         max_fee = U256::from(MIN_FEE_PER_PROOF);
     }
+    let total_fee = max_fee * proofs.len();
 
     let user_address = wallet.address();
     //TODO: Need to implement Aligned Error for Balance Error
-    let user_balance = get_balance_in_aligned(user_address, &args.rpc_url, network)
-        .await
-        .map_err(|_| {
-            SubmitError::GenericError("Failed to retrieve user balance from Aligned".to_string())
-        })?;
+    let user_balance = network.balance(user_address, &args.rpc_url).await?;
 
-    let format_max_fee = format_units(max_fee, "ether").map_err(|e| {
+    let format_total_fee = format_units(total_fee, "ether").map_err(|e| {
         error!("Unable to convert estimated proof submision price");
         SubmitError::GenericError(e.to_string())
     })?;
@@ -162,30 +463,26 @@ pub async fn submit_proof_to_aligned(
         SubmitError::GenericError(e.to_string())
     })?;
 
-    if user_balance < max_fee {
+    if user_balance < total_fee {
         info!(
             "Insufficient balance for {:?}: User Balance {:?} eth  < Proof Submission Fee {:?} eth",
-            user_address, format_user_balance, format_max_fee
+            user_address, format_user_balance, format_total_fee
         );
-        if Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
-            .with_prompt(format!(
+        // `--batcher-payment` funds later submissions, the deposit covers at least these proofs
+        let deposit = U256::from(args.batcher_payment).max(total_fee);
+        let format_deposit = format_units(deposit, "ether").map_err(|e| {
+            error!("Unable to convert batcher payment");
+            SubmitError::GenericError(e.to_string())
+        })?;
+        if confirm(
+            args,
+            format!(
                 "Would you like to deposit {:?} eth into Aligned to fund proof submission?",
-                format_max_fee
-            ))
-            .interact()
-            .map_err(|e| {
-                error!("Failed to read user input");
-                SubmitError::GenericError(e.to_string())
-            })?
-        {
+                format_deposit
+            ),
+        )? {
             info!("Submitting deposit to Batcher");
-            let Ok(tx_receipt) =
-                deposit_to_aligned(max_fee, signer, network).await
-            else {
-                return Err(SubmitError::GenericError(
-                    "Failed to Deposit Funds into the Batcher".to_string(),
-                ))?;
-            };
+            let tx_receipt = network.deposit(deposit, signer).await?;
             info!(
                 "Funds deposited successfully to Batcher payment contract. Tx: 0x{:x}",
                 tx_receipt.transaction_hash
@@ -198,73 +495,266 @@ pub async fn submit_proof_to_aligned(
         }
     }
 
-    if !Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
-        .with_prompt(format!(
-            "Would you like to pay {:?} eth to submit your proof to Aligned?",
-            format_max_fee
-        ))
-        .interact()
-        .map_err(|e| {
-            error!("Failed to read user input");
-            SubmitError::GenericError(e.to_string())
-        })?
-    {
+    let submitted = match proofs.len() {
+        1 => "your proof".to_string(),
+        count => format!("{} proofs", count),
+    };
+    if !confirm(
+        args,
+        format!(
+            "Would you like to pay {:?} eth to submit {} to Aligned?",
+            format_total_fee, submitted
+        ),
+    )? {
         info!("User declined to pay submission cost");
         return Err(SubmitError::GenericError(
             "User declined to pay submission cost".to_string(),
         ))?;
     }
 
-    let verification_data = VerificationData {
-        proving_system: proof_system_id,
-        proof,
-        proof_generator_addr: wallet.address(),
-        vm_program_code: Some(elf_data),
-        verification_key: None,
-        pub_input: pub_input.clone(),
-    };
-
-    let nonce = get_next_nonce(&args.rpc_url, wallet.address(), network)
-        .await?;
+    let verification_data: Vec<VerificationData> = proofs
+        .iter()
+        .map(|proof| VerificationData {
+            proving_system: proof.proving_system,
+            proof: proof.proof.clone(),
+            proof_generator_addr: wallet.address(),
+            vm_program_code: Some(proof.program.clone()),
+            verification_key: None,
+            pub_input: proof.pub_input.clone(),
+        })
+        .collect();
+    let max_fees = vec![max_fee; proofs.len()];
+
+    // The batcher expects the nonces of the proofs to follow each other, from the next nonce
+    let nonce = network.next_nonce(&args.rpc_url, wallet.address()).await?;
+
+    // Records are written before sending the proofs, so a dropped connection can be resumed
+    let mut records = vec![];
+    for (index, (proof, verification_data)) in proofs.iter().zip(&verification_data).enumerate() {
+        let record_path = SubmissionRecord::path(&proof.path);
+        let record = SubmissionRecord::new(
+            &args.network,
+            &proof.path,
+            verification_data,
+            nonce + index,
+            max_fee,
+        )
+        .and_then(|record| record.write(&record_path).map(|_| record))
+        .map_err(|e| SubmitError::IoError(record_path.clone(), e))?;
+        records.push((record_path, record));
+    }
 
-    info!("Submitting proof to Aligned for Verification");
+    info!("Submitting {} to Aligned for Verification", submitted);
 
-    let aligned_verification_data = submit_and_wait_verification(
-        &args.batcher_url,
-        &args.rpc_url,
-        network,
+    let aligned_verification_data = submit_with_retries(
+        &network,
+        args,
         &verification_data,
-        max_fee,
-        wallet,
+        &max_fees,
+        &wallet,
         nonce,
     )
-    .await?;
+    .await
+    .inspect_err(|_| {
+        for (record_path, record) in &records {
+            error!(
+                "Proof {:?} may still be batched, check it with `zkRust aligned status {}`",
+                record.proof,
+                record_path.display()
+            );
+        }
+    })?;
 
-    info!("Proof Submitted to Aligned!");
-    info!(
-        "https://explorer.alignedlayer.com/batches/0x{}",
-        hex::encode(aligned_verification_data.batch_merkle_root)
-    );
+    let mut submissions = vec![];
+    for ((aligned_verification_data, proof), (record_path, record)) in aligned_verification_data
+        .iter()
+        .zip(&proofs)
+        .zip(&mut records)
+    {
+        let batch_merkle_root = hex::encode(aligned_verification_data.batch_merkle_root);
+        let explorer_url = network.explorer_url(&batch_merkle_root);
+        info!("Proof Submitted to Aligned!");
+        if let Some(explorer_url) = &explorer_url {
+            info!("{}", explorer_url);
+        }
 
-    // If pub_input is None return empty
-    let pub_input = pub_input.unwrap_or(vec![]);
-    save_response(
-        PathBuf::from(&args.batch_inclusion_data_directory_path),
-        &aligned_verification_data,
-        &pub_input
-    )?;
+        // If pub_input is None return empty
+        let pub_input = proof.pub_input.as_deref().unwrap_or_default();
+        let verification_data_path = save_response(
+            PathBuf::from(&args.batch_inclusion_data_directory_path),
+            aligned_verification_data,
+            pub_input,
+        )?;
+        record.set_inclusion(aligned_verification_data, verification_data_path.clone());
+        record
+            .write(record_path)
+            .map_err(|e| SubmitError::IoError(record_path.clone(), e))?;
+        submissions.push(AlignedSubmission {
+            batch_merkle_root: format!("0x{}", batch_merkle_root),
+            explorer_url,
+            verification_data_path,
+            record: record_path.clone(),
+        });
+    }
     info!(
         "Aligned Verification Data saved {:?}",
         args.batch_inclusion_data_directory_path
     );
+    Ok(submissions)
+}
+
+/// Waits for the batches of the proofs in `records` to be verified on Aligned, marking each
+/// record verified as its batch is.
+pub async fn wait_for_verification(
+    records: &[PathBuf],
+    args: &SubmissionArgs,
+) -> Result<(), AlignedError> {
+    let network = network::resolve(&args.network, &args.network_profiles)
+        .map_err(|e| SubmitError::GenericError(e.to_string()))?;
+    let timeout = Duration::from_secs(args.verification_timeout);
+    for record_path in records {
+        let mut record = SubmissionRecord::read(record_path)
+            .map_err(|e| SubmitError::IoError(record_path.clone(), e))?;
+        let aligned_verification_data = record
+            .aligned_verification_data()
+            .map_err(|e| SubmitError::IoError(record_path.clone(), e))?
+            .ok_or_else(|| {
+                SubmitError::GenericError(format!("Proof {:?} has not been batched", record.proof))
+            })?;
+        network
+            .wait_for_verification(&args.rpc_url, &aligned_verification_data, timeout)
+            .await
+            .inspect_err(|_| {
+                error!(
+                    "Resume waiting with `zkRust aligned status 0x{} --wait`",
+                    record.proof_commitment
+                );
+            })?;
+        record.verified = true;
+        record
+            .write(record_path)
+            .map_err(|e| SubmitError::IoError(record_path.clone(), e))?;
+    }
     Ok(())
 }
 
+// Asks the user to accept a payment, `--yes` accepts it without a prompt.
+fn confirm(args: &SubmissionArgs, prompt: String) -> Result<bool, SubmitError> {
+    if args.assume_yes {
+        info!("{} Accepted by --yes", prompt);
+        return Ok(true);
+    }
+    Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
+        .with_prompt(prompt)
+        .interact()
+        .map_err(|e| {
+            error!("Failed to read user input");
+            SubmitError::GenericError(e.to_string())
+        })
+}
+
+// Sending the proofs again can not pay for them twice, the batcher rejects a proof with the nonce
+// of a proof it already holds unless its fee is higher.
+async fn submit_with_retries(
+    network: &AlignedNetwork,
+    args: &SubmissionArgs,
+    verification_data: &[VerificationData],
+    max_fees: &[U256],
+    wallet: &LocalWallet,
+    nonce: U256,
+) -> Result<Vec<AlignedVerificationData>, AlignedError> {
+    let timeout = Duration::from_secs(args.submit_timeout);
+    let mut attempt = 0;
+    loop {
+        let submission = network.submit_multiple(
+            &args.batcher_url,
+            verification_data,
+            max_fees,
+            wallet.clone(),
+            nonce,
+        );
+        let failure = match tokio::time::timeout(timeout, submission).await {
+            Ok(Ok(aligned_verification_data)) => return Ok(aligned_verification_data),
+            // The batcher may hold the proofs sent by the attempt that timed out
+            Ok(Err(e)) if !is_connection_error(&e) && attempt > 0 => {
+                return Err(wait_for_batch(
+                    network,
+                    args,
+                    wallet.address(),
+                    nonce,
+                    verification_data.len(),
+                    e,
+                )
+                .await)
+            }
+            Ok(Err(e)) if !is_connection_error(&e) => return Err(e),
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => format!("No answer from the batcher after {}s", args.submit_timeout),
+        };
+        if attempt == args.retries {
+            return Err(SubmitError::GenericError(failure))?;
+        }
+        attempt += 1;
+        warn!(
+            "{}, submitting again in {}s ({}/{})",
+            failure,
+            SUBMIT_RETRY_INTERVAL.as_secs(),
+            attempt,
+            args.retries
+        );
+        tokio::time::sleep(SUBMIT_RETRY_INTERVAL).await;
+    }
+}
+
+// Waits for the nonces of the proofs rejected by a retry to be used up by a batch, for at most
+// `--submit-timeout`. The batch of the proofs stays unknown, `zkRust aligned status` reports them.
+async fn wait_for_batch(
+    network: &AlignedNetwork,
+    args: &SubmissionArgs,
+    address: Address,
+    nonce: U256,
+    count: usize,
+    rejection: AlignedError,
+) -> AlignedError {
+    warn!(
+        "The batcher rejected the proofs sent again: {}, checking whether the previous attempt was batched",
+        rejection
+    );
+    let deadline = Instant::now() + Duration::from_secs(args.submit_timeout);
+    loop {
+        match network.next_nonce(&args.rpc_url, address).await {
+            Ok(next_nonce) if next_nonce >= nonce + count => {
+                return SubmitError::GenericError(format!(
+                    "The proofs with nonces {} to {} were batched but the batcher did not answer with their batch",
+                    nonce,
+                    nonce + (count - 1)
+                ))
+                .into()
+            }
+            Ok(_) if Instant::now() + SUBMIT_RETRY_INTERVAL <= deadline => {}
+            Ok(_) => return rejection,
+            Err(e) => return e,
+        }
+        tokio::time::sleep(SUBMIT_RETRY_INTERVAL).await;
+    }
+}
+
+fn is_connection_error(error: &AlignedError) -> bool {
+    matches!(
+        error,
+        AlignedError::SubmitError(
+            SubmitError::WebSocketConnectionError(_)
+                | SubmitError::WebSocketClosedUnexpectedlyError(_)
+                | SubmitError::NoResponseFromBatcher
+        )
+    )
+}
+
 fn save_response(
     batch_inclusion_data_directory_path: PathBuf,
     aligned_verification_data: &AlignedVerificationData,
     pub_input: &[u8],
-) -> Result<(), SubmitError> {
+) -> Result<PathBuf, SubmitError> {
     std::fs::create_dir_all(&batch_inclusion_data_directory_path)
         .map_err(|e| SubmitError::IoError(batch_inclusion_data_directory_path.clone(), e))?;
 
@@ -299,12 +789,15 @@ fn save_response(
         .map_err(|e| SubmitError::IoError(batch_inclusion_data_path.clone(), e))?;
     file.write_all(serde_json::to_string_pretty(&data).unwrap().as_bytes())
         .map_err(|e| SubmitError::IoError(batch_inclusion_data_path.clone(), e))?;
-    let current_dir = std::env::current_dir().map_err(|_| SubmitError::GenericError("Failed to get current directory".to_string()))?;
+    let current_dir = std::env::current_dir()
+        .map_err(|_| SubmitError::GenericError("Failed to get current directory".to_string()))?;
+
+    let batch_inclusion_data_path = current_dir.join(batch_inclusion_data_path);
 
     info!(
         "Saved batch inclusion data to {:?}",
-        current_dir.join(batch_inclusion_data_path)
+        batch_inclusion_data_path
     );
 
-    Ok(())
-}
\ No newline at end of file
+    Ok(batch_inclusion_data_path)
+}
@@ -0,0 +1,364 @@
+use clap::Args;
+use log::warn;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::utils;
+
+/// Constructs that fail or behave differently inside the riscv32im guest.
+/// Each entry is (lint name, pattern, message, suggestion).
+const GUEST_LINTS: &[(&str, &str, &str, &str)] = &[
+    (
+        "filesystem",
+        r"\bstd::fs\b|\bfs::\w+|\bFile::(open|create)\b",
+        "the filesystem is not available inside the zkVM",
+        "read the file in `input()` and pass its contents with `zk_rust_io::write`, or embed it with `include_bytes!`",
+    ),
+    (
+        "network",
+        r"\bstd::net\b|\bTcp(Stream|Listener)\b|\bUdpSocket\b|\breqwest::",
+        "the network is not available inside the zkVM",
+        "fetch the data in `input()` and pass it with `zk_rust_io::write`",
+    ),
+    (
+        "threads",
+        r"\bstd::thread\b|\bthread::spawn\b|\brayon\b|\.(into_)?par_iter(_mut)?\(",
+        "the zkVM guest is single threaded",
+        "use sequential iterators, the proof cost does not depend on parallelism",
+    ),
+    (
+        "time",
+        r"\bSystemTime::now\b|\bInstant::now\b",
+        "there is no system clock inside the zkVM",
+        "read the timestamp in `input()` and pass it with `zk_rust_io::write`",
+    ),
+    (
+        "host-only",
+        r"\bzk_rust_io::(host_args|project_dir)\b",
+        "`host_args()` and `project_dir()` are only available in `input()` and `output()`",
+        "read the arguments or files in `input()` and pass them with `zk_rust_io::write`",
+    ),
+];
+
+/// `zk_rust_io` calls zkRust only rewrites in one function of `src/main.rs`. Anywhere else they
+/// call the no-op functions of the zk_rust_io crate and silently do nothing.
+/// Each entry is (lint name, function, pattern, message, suggestion).
+const PLACED_IO_CALLS: &[(&str, &str, &str, &str, &str)] = &[
+    (
+        "misplaced-write",
+        "fn input()",
+        r"\bzk_rust_io::write(_private|_public|_slice)?\s*\(",
+        "inputs are only written from `input()`, this value never reaches the guest",
+        "call `zk_rust_io::write*` in `input()`, helpers can return the values to write",
+    ),
+    (
+        "misplaced-tracking",
+        "fn main()",
+        r"\bzk_rust_io::(track|cycle_count)\s*\(",
+        "cycles are only tracked in `main()`, this call measures nothing",
+        "call `zk_rust_io::track` in `main()` around the call of the helper",
+    ),
+];
+
+const RNG_PATTERN: &str = r"\bthread_rng\b|\brand::random\b|\bOsRng\b";
+const INCLUDE_PATTERN: &str = r#"include_(bytes|str)!\(\s*"([^"]+)"\s*\)"#;
+
+#[derive(Args, Debug)]
+pub struct LintArgs {
+    pub guest_path: String,
+}
+
+/// Construct found in the guest program that is not supported by the zkVM.
+#[derive(Debug, Serialize)]
+pub struct LintWarning {
+    pub lint: &'static str,
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+    pub suggestion: String,
+}
+
+/// Result of the `lint` command.
+#[derive(Debug, Serialize)]
+pub struct LintOutput {
+    pub warnings: Vec<LintWarning>,
+}
+
+/// Scans the `main()` body of the program in `guest_path` and the modules it declares for
+/// constructs that cannot run inside the zkVM guest, and the whole program for `zk_rust_io`
+/// calls zkRust does not rewrite.
+pub fn lint_guest(guest_path: &Path) -> io::Result<Vec<LintWarning>> {
+    let main_path = guest_path.join("src/main.rs");
+    let main_rs = fs::read_to_string(&main_path)?;
+    let mut warnings = misplaced_io_calls(guest_path)?;
+
+    let Ok(function_bodies) =
+        utils::extract_function_bodies(&main_path, vec!["fn main()".to_string()])
+    else {
+        return Err(io::Error::other("Failed to extract function bodies"));
+    };
+    let Some(main_body) = function_bodies.first().filter(|body| !body.is_empty()) else {
+        return Ok(warnings);
+    };
+
+    let lints = Lints::new(guest_path)?;
+    if let Some(index) = main_rs.find(main_body.as_str()) {
+        let first_line = main_rs[..index].matches('\n').count() + 1;
+        lints.check(&main_path, main_body, first_line, &mut warnings);
+    }
+
+    // Modules declared next to `main()` are compiled into the guest as a whole
+    for (module, code) in project_modules(guest_path, &main_path, &main_rs) {
+        lints.check(&module, &code, 1, &mut warnings);
+    }
+    Ok(warnings)
+}
+
+/// Finds the `zk_rust_io` calls made outside of the function zkRust rewrites them in, in
+/// `src/main.rs` and the modules it declares.
+pub fn misplaced_io_calls(guest_path: &Path) -> io::Result<Vec<LintWarning>> {
+    let main_path = guest_path.join("src/main.rs");
+    let main_rs = fs::read_to_string(&main_path)?;
+    let mut files = vec![(main_path.clone(), main_rs.clone())];
+    files.extend(project_modules(guest_path, &main_path, &main_rs));
+
+    let mut warnings = vec![];
+    for (lint, function, pattern, message, suggestion) in PLACED_IO_CALLS {
+        let regex = Regex::new(pattern).map_err(io::Error::other)?;
+        let Ok(function_bodies) =
+            utils::extract_function_bodies(&main_path, vec![function.to_string()])
+        else {
+            return Err(io::Error::other("Failed to extract function bodies"));
+        };
+        // Byte range of the body of `function` in `src/main.rs`, where the calls are rewritten
+        let rewritten = function_bodies
+            .first()
+            .filter(|body| !body.is_empty())
+            .and_then(|body| {
+                main_rs
+                    .find(body.as_str())
+                    .map(|start| start..start + body.len())
+            });
+
+        for (file, code) in &files {
+            let mut line_start = 0;
+            for (offset, line) in code.lines().enumerate() {
+                let code_line = line.split("//").next().unwrap_or_default();
+                let misplaced = regex.find_iter(code_line).any(|call| {
+                    *file != main_path
+                        || !rewritten
+                            .as_ref()
+                            .is_some_and(|range| range.contains(&(line_start + call.start())))
+                });
+                if misplaced {
+                    warnings.push(LintWarning {
+                        lint,
+                        file: file.clone(),
+                        line: offset + 1,
+                        message: message.to_string(),
+                        suggestion: suggestion.to_string(),
+                    });
+                }
+                line_start += line.len() + 1;
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+/// Fails on the calls found by [`misplaced_io_calls`], the program would not do what it reads.
+pub fn reject_misplaced_io_calls(guest_path: &Path) -> io::Result<()> {
+    let warnings = misplaced_io_calls(guest_path)?;
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    let locations: Vec<String> = warnings
+        .iter()
+        .map(|warning| {
+            format!(
+                "{}:{}: {}",
+                warning.file.display(),
+                warning.line,
+                warning.message
+            )
+        })
+        .collect();
+    Err(io::Error::other(format!(
+        "zk_rust_io calls zkRust cannot rewrite, {}",
+        locations.join("; ")
+    )))
+}
+
+/// Logs the warnings found by [`lint_guest`].
+pub fn log_warnings(warnings: &[LintWarning]) {
+    for warning in warnings {
+        warn!(
+            "{}:{}: {} [{}]",
+            warning.file.display(),
+            warning.line,
+            warning.message,
+            warning.lint
+        );
+        warn!("  help: {}", warning.suggestion);
+    }
+}
+
+struct Lints {
+    project_dir: PathBuf,
+    patterns: Vec<(&'static str, Regex, &'static str, &'static str)>,
+    rng: Option<Regex>,
+    include: Regex,
+}
+
+impl Lints {
+    fn new(project_dir: &Path) -> io::Result<Self> {
+        let patterns = GUEST_LINTS
+            .iter()
+            .map(|(lint, pattern, message, suggestion)| {
+                Regex::new(pattern)
+                    .map(|regex| (*lint, regex, *message, *suggestion))
+                    .map_err(io::Error::other)
+            })
+            .collect::<io::Result<_>>()?;
+        // Randomness works in the guest once getrandom is given a custom implementation
+        let rng = if has_getrandom_shim(&project_dir.join("Cargo.toml")) {
+            None
+        } else {
+            Some(Regex::new(RNG_PATTERN).map_err(io::Error::other)?)
+        };
+        Ok(Lints {
+            project_dir: project_dir.to_path_buf(),
+            patterns,
+            rng,
+            include: Regex::new(INCLUDE_PATTERN).map_err(io::Error::other)?,
+        })
+    }
+
+    fn check(&self, file: &Path, code: &str, first_line: usize, warnings: &mut Vec<LintWarning>) {
+        for (offset, line) in code.lines().enumerate() {
+            let line_number = first_line + offset;
+            // Imports alone are harmless, the lints match where the items are used
+            let line = line.split("//").next().unwrap_or_default();
+            if line.trim_start().starts_with("use ") {
+                continue;
+            }
+            let mut warn = |lint, message: String, suggestion: &str| {
+                warnings.push(LintWarning {
+                    lint,
+                    file: file.to_path_buf(),
+                    line: line_number,
+                    message,
+                    suggestion: suggestion.to_string(),
+                })
+            };
+
+            for (lint, regex, message, suggestion) in &self.patterns {
+                if regex.is_match(line) {
+                    warn(lint, message.to_string(), suggestion);
+                }
+            }
+            if self.rng.as_ref().is_some_and(|rng| rng.is_match(line)) {
+                warn(
+                    "randomness",
+                    "there is no OS randomness inside the zkVM".to_string(),
+                    "seed a `StdRng` with a value written in `input()`, or add `getrandom = { version = \"0.2\", features = [\"custom\"] }` to Cargo.toml",
+                );
+            }
+            for capture in self.include.captures_iter(line) {
+                let included = &capture[2];
+                if !self.is_copied(file, included) {
+                    warn(
+                        "include",
+                        format!("`{}` is not copied into the zkVM workspace", included),
+                        "only `src/` and `lib/` are copied, move the file into `src/` and include it with a path relative to the source file",
+                    );
+                }
+            }
+        }
+    }
+
+    // zkRust copies `src/` and `lib/` into the workspace, `include_*!` paths are relative to the source file.
+    fn is_copied(&self, file: &Path, included: &str) -> bool {
+        let Some(dir) = file.parent() else {
+            return false;
+        };
+        let (Ok(path), Ok(project_dir)) = (
+            dir.join(included).canonicalize(),
+            self.project_dir.canonicalize(),
+        ) else {
+            return false;
+        };
+        path.starts_with(project_dir.join("src")) || path.starts_with(project_dir.join("lib"))
+    }
+}
+
+// Sources of the modules declared by `src/main.rs`, recursively, and of the `lib/` crate.
+fn project_modules(guest_path: &Path, main_path: &Path, main_rs: &str) -> Vec<(PathBuf, String)> {
+    let mut modules = declared_modules(main_path, main_rs);
+    let lib_rs = guest_path.join("lib/src/lib.rs");
+    if lib_rs.exists() {
+        modules.push(lib_rs);
+    }
+    let mut visited: Vec<(PathBuf, String)> = vec![];
+    while let Some(module) = modules.pop() {
+        if visited.iter().any(|(path, _)| *path == module) {
+            continue;
+        }
+        let Ok(code) = fs::read_to_string(&module) else {
+            continue;
+        };
+        modules.extend(declared_modules(&module, &code));
+        visited.push((module, code));
+    }
+    visited
+}
+
+// Files of the `mod` declarations in `code`, following the 2018 module layout.
+fn declared_modules(file: &Path, code: &str) -> Vec<PathBuf> {
+    let Some(dir) = file.parent() else {
+        return vec![];
+    };
+    // Modules of `foo.rs` live in `foo/`, modules of `main.rs`, `lib.rs` and `mod.rs` next to them
+    let dir = match file.file_stem().and_then(|stem| stem.to_str()) {
+        Some("main") | Some("lib") | Some("mod") | None => dir.to_path_buf(),
+        Some(stem) => dir.join(stem),
+    };
+    let Ok(mod_regex) = Regex::new(r"^\s*(pub(\([^)]*\))?\s+)?mod\s+(\w+)\s*;") else {
+        return vec![];
+    };
+    code.lines()
+        .filter_map(|line| mod_regex.captures(line))
+        .filter_map(|capture| {
+            let name = &capture[3];
+            [
+                dir.join(format!("{}.rs", name)),
+                dir.join(name).join("mod.rs"),
+            ]
+            .into_iter()
+            .find(|path| path.exists())
+        })
+        .collect()
+}
+
+fn has_getrandom_shim(cargo_toml: &Path) -> bool {
+    let Ok(manifest) = fs::read_to_string(cargo_toml) else {
+        return false;
+    };
+    let Ok(manifest) = manifest.parse::<toml::Table>() else {
+        return false;
+    };
+    manifest
+        .get("dependencies")
+        .and_then(|dependencies| dependencies.get("getrandom"))
+        .and_then(|getrandom| getrandom.get("features"))
+        .and_then(|features| features.as_array())
+        .is_some_and(|features| {
+            features
+                .iter()
+                .any(|feature| feature.as_str() == Some("custom"))
+        })
+}
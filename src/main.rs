@@ -1,13 +1,27 @@
 use aligned_sdk::core::types::ProvingSystemId;
-use clap::{Parser, Subcommand};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use env_logger::Env;
 use log::error;
 use log::info;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::PathBuf;
+use log::warn;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use tokio::io;
-use zkRust::{risc0, sp1, submit_proof_to_aligned, utils, ProofArgs};
+use zkRust::aligned::{self, AlignedArgs, AlignedCommand};
+use zkRust::batch::{self, BatchOutput, ProveArgs};
+use zkRust::cache::{self, CacheArgs, CacheCommand, ProgramCache};
+use zkRust::config::{self, Config};
+use zkRust::expand::{self, EjectArgs, ExpandArgs};
+use zkRust::input::{HostInput, InputFile};
+use zkRust::lint::{self, LintArgs, LintOutput};
+use zkRust::mock_batcher;
+use zkRust::output::{self, OutputFormat, ProofArtifacts, ProofOutput};
+use zkRust::precompiles::{self, PrecompilesArgs, PrecompilesOutput};
+use zkRust::profile::{self, ProfileArgs, ProfileOutput};
+use zkRust::scaffold::{self, NewArgs};
+use zkRust::source_map::SourceMaps;
+use zkRust::submit::{self, SubmitArgs};
+use zkRust::{risc0, sp1, submit_proof_to_aligned, utils, Backend, ProofArgs, ProofType};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -15,10 +29,38 @@ use zkRust::{risc0, sp1, submit_proof_to_aligned, utils, ProofArgs};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    #[arg(
+        long = "output",
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        help = "Format of the command result, `json` prints a single object on stdout and logs on stderr"
+    )]
+    output: OutputFormat,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    #[clap(about = "Create a new zkRust project, optionally from one of the examples")]
+    New(NewArgs),
+    #[clap(about = "Print the guest and host programs zkRust generates for a backend")]
+    Expand(ExpandArgs),
+    #[clap(about = "Write the generated backend workspace into a standalone project")]
+    Eject(EjectArgs),
+    #[clap(about = "Check a program for code that cannot run inside a zkVM")]
+    Lint(LintArgs),
+    #[clap(about = "Manage the cache of built programs and proving keys")]
+    Cache(CacheArgs),
+    #[clap(about = "Generate proofs of execution of a program, over each file of --inputs-dir")]
+    Prove(ProveArgs),
+    #[clap(about = "Execute a program and write a per-function cycle profile for flamegraphs")]
+    Profile(ProfileArgs),
+    #[clap(about = "Show the dependencies accelerated by --precompiles and the cycles they save")]
+    Precompiles(PrecompilesArgs),
+    #[clap(about = "Submit proofs already generated to Aligned in one batcher call")]
+    Submit(SubmitArgs),
+    #[clap(about = "Tools for submitting proofs to Aligned")]
+    Aligned(AlignedArgs),
     #[clap(about = "Generate a proof of execution of a program using SP1")]
     ProveSp1(ProofArgs),
     #[clap(about = "Generate a proof of execution of a program using RISC0")]
@@ -26,271 +68,744 @@ enum Commands {
 }
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
+async fn main() -> ExitCode {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Err(e) = apply_config(&mut cli.command, &matches) {
+        return output::report::<()>(cli.output, Err(e));
+    }
 
     match &cli.command {
-        Commands::ProveSp1(args) => {
-            info!("Proving with SP1, program in: {}", args.guest_path);
-            // Perform sanitation checks on directory
-            let proof_data_dir = PathBuf::from(&args.proof_data_directory_path);
-            if !proof_data_dir.exists() {
-                std::fs::create_dir_all(proof_data_dir).unwrap_or(info!(
-                    "Saving Proofs to: {:?}",
-                    &args.proof_data_directory_path
-                ));
+        Commands::New(args) => {
+            let result = scaffold::new_project(&args.path, args.template, args.lib);
+            if let Ok(project) = &result {
+                info!(
+                    "Created zkRust project `{}` in {:?}",
+                    project.name, project.path
+                );
             }
-            if utils::validate_directory_structure(&args.guest_path) {
-                let Some(home_dir) = dirs::home_dir() else {
-                    error!("Failed to locate home directory");
-                    return Ok(());
-                };
-                let Ok(current_dir) = std::env::current_dir() else {
-                    error!("Failed to locate current directory");
-                    return Ok(());
-                };
-                let home_dir = home_dir.join(".zkRust");
-                utils::prepare_workspace(
-                    &PathBuf::from(&args.guest_path),
-                    &home_dir.join(sp1::SP1_SRC_DIR),
-                    &home_dir.join(sp1::SP1_GUEST_CARGO_TOML),
-                    &home_dir.join("workspaces/sp1/script"),
-                    &home_dir.join("workspaces/sp1/script/Cargo.toml"),
-                    &home_dir.join(sp1::SP1_BASE_HOST_CARGO_TOML),
-                    &home_dir.join(sp1::SP1_BASE_GUEST_CARGO_TOML),
-                )?;
-
-                let Ok(imports) = utils::get_imports(&home_dir.join(sp1::SP1_GUEST_MAIN)) else {
-                    error!("Failed to extract imports");
-                    return Ok(());
-                };
-
-                let main_path = home_dir.join(sp1::SP1_GUEST_MAIN);
-                let Ok(function_bodies) = utils::extract_function_bodies(
-                    &main_path,
-                    vec![
-                        "fn main()".to_string(),
-                        "fn input()".to_string(),
-                        "fn output()".to_string(),
-                    ],
-                ) else {
-                    error!("Failed to extract function bodies");
-                    return Ok(());
-                };
-                /*
-                    Adds header to the guest & replace I/O imports
-                    risc0:
-
-                        #![no_main]
-                        sp1_zkvm::entrypoint!(main);
-                */
-                utils::prepare_guest(
-                    &imports,
-                    &function_bodies[0],
-                    sp1::SP1_GUEST_PROGRAM_HEADER,
-                    sp1::SP1_IO_READ,
-                    sp1::SP1_IO_COMMIT,
-                    &home_dir.join(sp1::SP1_GUEST_MAIN),
-                )?;
-                sp1::prepare_host(
-                    &function_bodies[1],
-                    &function_bodies[2],
-                    &imports,
-                    &home_dir.join(sp1::SP1_BASE_HOST),
-                    &home_dir.join(sp1::SP1_HOST_MAIN),
-                )?;
-
-                if args.precompiles {
-                    let mut toml_file = OpenOptions::new()
-                        .append(true) // Open the file in append mode
-                        .open(home_dir.join(sp1::SP1_GUEST_CARGO_TOML))?;
-
-                    writeln!(toml_file, "{}", sp1::SP1_ACCELERATION_IMPORT)?;
+            output::report(cli.output, result)
+        }
+        Commands::Expand(args) => {
+            let result = zkrust_home().and_then(|home_dir| {
+                expand::expand(&PathBuf::from(&args.guest_path), args.backend, &home_dir)
+            });
+            if let (OutputFormat::Human, Ok(expanded)) = (cli.output, &result) {
+                for file in [&expanded.guest, &expanded.host] {
+                    println!("// {}\n{}\n", file.path.display(), file.contents);
                 }
-
-                let script_dir = home_dir.join(sp1::SP1_SCRIPT_DIR);
-                if sp1::generate_sp1_proof(&script_dir, &current_dir)?.success() {
-                    info!("SP1 proof and ELF generated");
-
-                    utils::replace(
-                        &home_dir.join(sp1::SP1_GUEST_CARGO_TOML),
-                        sp1::SP1_ACCELERATION_IMPORT,
-                        "",
-                    )?;
-
-                    // Submit to aligned
-                    if args.submit_to_aligned {
-                        submit_proof_to_aligned(
-                            sp1::SP1_PROOF_PATH,
-                            sp1::SP1_ELF_PATH,
-                            Some(sp1::SP1_PUB_INPUT_PATH),
-                            args,
-                            ProvingSystemId::SP1,
-                        )
-                        .await
-                        .map_err(|e| {
-                            error!("Proof not submitted to Aligned");
-                            io::Error::other(e.to_string())
-                        })?;
-                        info!("SP1 proof submitted and verified on Aligned");
-                    }
-
-                    std::fs::copy(
-                        home_dir.join(sp1::SP1_BASE_HOST_FILE),
-                        home_dir.join(sp1::SP1_HOST_MAIN),
-                    )
-                    .map_err(|e| {
-                        error!("Failed to clear SP1 host file");
-                        e
-                    })?;
-                    return Ok(());
+            }
+            output::report(cli.output, result)
+        }
+        Commands::Eject(args) => {
+            let result = zkrust_home().and_then(|home_dir| {
+                expand::eject(
+                    &PathBuf::from(&args.guest_path),
+                    args.backend,
+                    &home_dir,
+                    &args.out_dir,
+                )
+            });
+            if let Ok(ejected) = &result {
+                info!(
+                    "{:?} workspace written to {:?}",
+                    ejected.backend, ejected.path
+                );
+            }
+            output::report(cli.output, result)
+        }
+        Commands::Lint(args) => {
+            let guest_path = PathBuf::from(&args.guest_path);
+            let result = if utils::validate_directory_structure(&args.guest_path) {
+                lint::lint_guest(&guest_path).map(|warnings| LintOutput { warnings })
+            } else {
+                Err(io::Error::other(
+                    "zkRust directory structure invalid please consult the README",
+                ))
+            };
+            if let Ok(linted) = &result {
+                lint::log_warnings(&linted.warnings);
+                info!("{} warning(s) found", linted.warnings.len());
+            }
+            output::report(cli.output, result)
+        }
+        Commands::Cache(args) => match args.command {
+            CacheCommand::Clean => {
+                let result = zkrust_home().and_then(|home_dir| cache::clean(&home_dir));
+                if let Ok(cleaned) = &result {
+                    info!("Removed the cache in {:?}", cleaned.path);
                 }
-                info!("SP1 proof generation failed");
+                output::report(cli.output, result)
+            }
+        },
+        Commands::Submit(args) => output::report(cli.output, submit::submit(args).await),
+        Commands::Aligned(args) => match &args.command {
+            AlignedCommand::MockBatcher(args) => {
+                output::report(cli.output, mock_batcher::run(args).await)
+            }
+            AlignedCommand::Status(args) => output::report(cli.output, aligned::status(args).await),
+        },
+        Commands::Prove(args) => match (&args.inputs_dir, args.backend) {
+            (Some(inputs_dir), Some(backend)) => output::report_batch(
+                cli.output,
+                prove_batch(args, backend, inputs_dir, cli.output),
+            ),
+            (None, Some(Backend::Sp1)) => {
+                output::report(cli.output, prove_sp1(&args.proof, cli.output).await)
+            }
+            (None, Some(Backend::Risc0)) => {
+                output::report(cli.output, prove_risc0(&args.proof, cli.output).await)
+            }
+            (_, None) => output::report::<()>(cli.output, Err(config::missing_backend())),
+        },
+        Commands::Profile(args) => {
+            let result = args
+                .backend
+                .ok_or_else(config::missing_backend)
+                .and_then(|backend| profile(args, backend, cli.output));
+            output::report(cli.output, result)
+        }
+        Commands::Precompiles(args) => {
+            let result = args
+                .backend
+                .ok_or_else(config::missing_backend)
+                .and_then(|backend| estimate_precompiles(args, backend, cli.output));
+            if let Ok(estimate) = &result {
+                estimate.log();
+            }
+            output::report(cli.output, result)
+        }
+        Commands::ProveSp1(args) => output::report(cli.output, prove_sp1(args, cli.output).await),
+        Commands::ProveRisc0(args) => {
+            output::report(cli.output, prove_risc0(args, cli.output).await)
+        }
+    }
+}
 
-                // Clear host
-                std::fs::copy(
-                    home_dir.join(sp1::SP1_BASE_HOST_FILE),
-                    home_dir.join(sp1::SP1_HOST_MAIN),
-                )?;
-                return Ok(());
-            } else {
-                error!("zkRust directory structure invalid please consult the README",);
+// Flags given on the command line override zkrust.toml of the project, which overrides the one
+// in the zkRust home, which overrides the defaults of the flags.
+fn apply_config(command: &mut Commands, matches: &ArgMatches) -> io::Result<()> {
+    let Some((_, matches)) = matches.subcommand() else {
+        return Ok(());
+    };
+    let (proof_args, backend) = match command {
+        Commands::Prove(args) => (&mut args.proof, Some(&mut args.backend)),
+        Commands::Profile(args) => (&mut args.proof, Some(&mut args.backend)),
+        Commands::Precompiles(args) => (&mut args.proof, Some(&mut args.backend)),
+        Commands::ProveSp1(args) | Commands::ProveRisc0(args) => (args, None),
+        // Proofs are submitted from anywhere, only the zkrust.toml of the current directory applies
+        Commands::Submit(args) => {
+            return Config::load(&zkrust_home()?, Path::new("."))?
+                .apply_submission(&mut args.aligned, matches);
+        }
+        Commands::Aligned(AlignedArgs {
+            command: AlignedCommand::Status(args),
+        }) => {
+            let Some((_, matches)) = matches.subcommand() else {
                 return Ok(());
-            }
+            };
+            return Config::load(&zkrust_home()?, Path::new("."))?
+                .apply_submission(&mut args.aligned, matches);
         }
+        _ => return Ok(()),
+    };
+    let config = Config::load(&zkrust_home()?, Path::new(&proof_args.guest_path))?;
+    config.apply(proof_args, matches)?;
+    if let Some(backend) = backend {
+        config.apply_backend(backend)?;
+    }
+    Ok(())
+}
 
-        Commands::ProveRisc0(args) => {
-            info!("Proving with Risc0, program in: {}", args.guest_path);
-
-            // Perform sanitation checks on directory
-            if utils::validate_directory_structure(&args.guest_path) {
-                let proof_data_dir = PathBuf::from(&args.proof_data_directory_path);
-                if !proof_data_dir.exists() {
-                    std::fs::create_dir_all(proof_data_dir).unwrap_or(info!(
-                        "Saving generated proofs to: {:?}",
-                        &args.proof_data_directory_path
-                    ));
-                }
-                let Some(home_dir) = dirs::home_dir() else {
-                    error!("Failed to locate home directory");
-                    return Ok(());
-                };
-                let Ok(current_dir) = std::env::current_dir() else {
-                    error!("Failed to locate current directory");
-                    return Ok(());
-                };
-                let home_dir = home_dir.join(".zkRust");
-                utils::prepare_workspace(
-                    &PathBuf::from(&args.guest_path),
-                    &home_dir.join(risc0::RISC0_SRC_DIR),
-                    &home_dir.join(risc0::RISC0_GUEST_CARGO_TOML),
-                    &home_dir.join("workspaces/risc0/host"),
-                    &home_dir.join("workspaces/risc0/host/Cargo.toml"),
-                    &home_dir.join(risc0::RISC0_BASE_HOST_CARGO_TOML),
-                    &home_dir.join(risc0::RISC0_BASE_GUEST_CARGO_TOML),
-                )?;
-
-                let Ok(imports) = utils::get_imports(&home_dir.join(risc0::RISC0_GUEST_MAIN))
-                else {
-                    error!("Failed to extract imports");
-                    return Ok(());
-                };
-                let main_path = home_dir.join(risc0::RISC0_GUEST_MAIN);
-                let Ok(function_bodies) = utils::extract_function_bodies(
-                    &main_path,
-                    vec![
-                        "fn main()".to_string(),
-                        "fn input()".to_string(),
-                        "fn output()".to_string(),
-                    ],
-                ) else {
-                    error!("Failed to extract function bodies");
-                    return Ok(());
-                };
-
-                /*
-                    Adds header to the guest & replace I/O imports
-                    risc0:
-
-                        #![no_main]
-                        risc0_zkvm::guest::entry!(main);
-                */
-                utils::prepare_guest(
-                    &imports,
-                    &function_bodies[0],
-                    risc0::RISC0_GUEST_PROGRAM_HEADER,
-                    risc0::RISC0_IO_READ,
-                    risc0::RISC0_IO_COMMIT,
-                    &home_dir.join(risc0::RISC0_GUEST_MAIN),
-                )?;
-                risc0::prepare_host(
-                    &function_bodies[1],
-                    &function_bodies[2],
-                    &imports,
-                    &home_dir.join(risc0::RISC0_BASE_HOST),
-                    &home_dir.join(risc0::RISC0_HOST_MAIN),
-                )?;
-
-                if args.precompiles {
-                    let mut toml_file = OpenOptions::new()
-                        .append(true)
-                        .open(home_dir.join(risc0::RISC0_GUEST_CARGO_TOML))?;
-
-                    writeln!(toml_file, "{}", risc0::RISC0_ACCELERATION_IMPORT)?;
-                }
+/// zkRust home directory holding the backend workspaces
+fn zkrust_home() -> io::Result<PathBuf> {
+    let Some(home_dir) = dirs::home_dir() else {
+        return Err(io::Error::other("Failed to locate home directory"));
+    };
+    Ok(home_dir.join(".zkRust"))
+}
 
-                let workspace_dir = home_dir.join(risc0::RISC0_WORKSPACE_DIR);
-                if risc0::generate_risc0_proof(&workspace_dir, &current_dir)?.success() {
-                    info!("Risc0 proof and Image ID generated");
-
-                    utils::replace(
-                        &home_dir.join(risc0::RISC0_GUEST_CARGO_TOML),
-                        risc0::RISC0_ACCELERATION_IMPORT,
-                        "",
-                    )?;
-
-                    // Submit to aligned
-                    if args.submit_to_aligned {
-                        submit_proof_to_aligned(
-                            risc0::PROOF_FILE_PATH,
-                            risc0::IMAGE_ID_FILE_PATH,
-                            Some(risc0::PUBLIC_INPUT_FILE_PATH),
-                            args,
-                            ProvingSystemId::Risc0,
-                        )
-                        .await
-                        .map_err(|e| {
-                            error!("Error submitting proofs to Aligned: {:?}", e);
-                            io::Error::other(e.to_string())
-                        })?;
-
-                        info!("Risc0 proof submitted and verified on Aligned");
-                    }
-
-                    // Clear Host file
-                    std::fs::copy(
-                        home_dir.join(risc0::RISC0_BASE_HOST_FILE),
-                        home_dir.join(risc0::RISC0_HOST_MAIN),
-                    )
-                    .map_err(|e| {
-                        error!("Failed to clear Risc0 host file");
-                        e
-                    })?;
-                    return Ok(());
-                }
-                info!("Risc0 proof generation failed");
+// Artifacts only written by some programs, like the public inputs metadata, must not outlive a previous run.
+fn remove_stale_artifact(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
 
-                // Clear Host file
-                std::fs::copy(
-                    home_dir.join(risc0::RISC0_BASE_HOST_FILE),
-                    home_dir.join(risc0::RISC0_HOST_MAIN),
-                )?;
-                return Ok(());
-            } else {
-                error!("zkRust directory structure incorrect please consult the README",);
-                return Ok(());
+// Warnings don't stop the build, a program may only use these constructs on unreachable paths.
+fn lint_before_build(guest_path: &str) {
+    match lint::lint_guest(&PathBuf::from(guest_path)) {
+        Ok(warnings) => lint::log_warnings(&warnings),
+        Err(e) => error!("Failed to lint program: {}", e),
+    }
+}
+
+// Values given with `--input` replace the `input()` function of the program.
+fn resolve_input(args: &ProofArgs, home_dir: &Path) -> io::Result<Option<InputFile>> {
+    let Some(input) = &args.input else {
+        return Ok(None);
+    };
+    let input_file = InputFile::resolve(input, args.input_format, home_dir)?;
+    input_file.validate(&PathBuf::from(&args.guest_path))?;
+    Ok(Some(input_file))
+}
+
+// The host runs inside the workspace, the project root is passed for `zk_rust_io::project_dir()`.
+fn host_envs(
+    args: &ProofArgs,
+    input_file: Option<&InputFile>,
+) -> io::Result<Vec<(&'static str, String)>> {
+    let project_dir = PathBuf::from(&args.guest_path).canonicalize()?;
+    let mut envs = vec![
+        (utils::PROJECT_DIR_ENV, project_dir.display().to_string()),
+        (
+            utils::PROOF_DATA_DIR_ENV,
+            proof_data_dir(args)?.display().to_string(),
+        ),
+    ];
+    envs.extend(
+        args.proof_type
+            .map(|proof_type| (utils::PROOF_TYPE_ENV, proof_type.as_str().to_string())),
+    );
+    envs.extend(input_file.map(InputFile::envs).unwrap_or_default());
+    Ok(envs)
+}
+
+// Directory the host writes the proofs to, `--proof-data-path` resolved from the current directory.
+fn proof_data_dir(args: &ProofArgs) -> io::Result<PathBuf> {
+    Ok(std::env::current_dir()?.join(&args.proof_data_directory_path))
+}
+
+// Builds the generated workspace, its compile errors are printed pointing at the user's sources.
+fn build_workspace(
+    backend: Backend,
+    home_dir: &Path,
+    envs: &[(&str, String)],
+    source_maps: &SourceMaps,
+    output_format: OutputFormat,
+) -> io::Result<()> {
+    let errors = backend
+        .build(home_dir, envs, source_maps, output_format)
+        .inspect_err(|_| {
+            let _ = backend.clear_host(home_dir);
+        })?;
+    if errors > 0 {
+        backend.clear_host(home_dir)?;
+        return Err(io::Error::other(format!(
+            "{} compile error(s) in the program",
+            errors
+        )));
+    }
+    Ok(())
+}
+
+async fn prove_sp1(args: &ProofArgs, output_format: OutputFormat) -> io::Result<ProofOutput> {
+    info!("Proving with SP1, program in: {}", args.guest_path);
+    if args.submit_to_aligned && args.proof_type == Some(ProofType::Core) {
+        return Err(io::Error::other(
+            "Aligned only verifies compressed SP1 proofs, remove --proof-type core",
+        ));
+    }
+    // Perform sanitation checks on directory
+    let proof_data_dir = proof_data_dir(args)?;
+    if !proof_data_dir.exists() {
+        std::fs::create_dir_all(&proof_data_dir).unwrap_or(info!(
+            "Saving Proofs to: {:?}",
+            &args.proof_data_directory_path
+        ));
+    }
+    if !utils::validate_directory_structure(&args.guest_path) {
+        return Err(io::Error::other(
+            "zkRust directory structure invalid please consult the README",
+        ));
+    }
+    lint_before_build(&args.guest_path);
+    let home_dir = zkrust_home()?;
+    let Ok(current_dir) = std::env::current_dir() else {
+        return Err(io::Error::other("Failed to locate current directory"));
+    };
+    let input_file = resolve_input(args, &home_dir)?;
+    let host_input = match input_file {
+        Some(_) => HostInput::File,
+        None => HostInput::Function,
+    };
+    let source_maps = sp1::prepare(&PathBuf::from(&args.guest_path), &home_dir, host_input)?;
+
+    if !args.precompiles {
+        precompiles::suggest(Backend::Sp1, &PathBuf::from(&args.guest_path), &home_dir);
+    }
+    let _precompiles = args
+        .precompiles
+        .then(|| Backend::Sp1.apply_precompiles(&PathBuf::from(&args.guest_path), &home_dir))
+        .transpose()?;
+
+    remove_stale_artifact(&proof_data_dir.join(sp1::SP1_PUBLIC_INPUTS_PATH))?;
+    let mut envs = host_envs(args, input_file.as_ref())?;
+    envs.extend(
+        ProgramCache::new(&home_dir, Backend::Sp1, Path::new(&args.guest_path))?
+            .prepare(&home_dir)?,
+    );
+    build_workspace(Backend::Sp1, &home_dir, &envs, &source_maps, output_format)?;
+    let status = Backend::Sp1.generate_proof(
+        &home_dir,
+        &current_dir,
+        output_format.child_stdout(),
+        &envs,
+        &args.host_args,
+    );
+    Backend::Sp1.clear_host(&home_dir).inspect_err(|_| {
+        error!("Failed to clear SP1 host file");
+    })?;
+    if !status?.success() {
+        return Err(io::Error::other("SP1 proof generation failed"));
+    }
+    info!("SP1 proof and ELF generated");
+
+    let mut proof_output = ProofOutput::from_artifacts(
+        "sp1",
+        ProofArtifacts::new(
+            proof_data_dir.join(sp1::SP1_PROOF_PATH),
+            proof_data_dir.join(sp1::SP1_ELF_PATH),
+            proof_data_dir.join(sp1::SP1_PUB_INPUT_PATH),
+            proof_data_dir.join(sp1::SP1_PUBLIC_INPUTS_PATH),
+        ),
+        &proof_data_dir.join(sp1::SP1_VKEY_PATH),
+        &proof_data_dir.join(sp1::SP1_CYCLES_PATH),
+    )?;
+
+    // Submit to aligned
+    if args.submit_to_aligned {
+        let submission = submit_proof_to_aligned(
+            &proof_data_dir.join(sp1::SP1_PROOF_PATH),
+            &proof_data_dir.join(sp1::SP1_ELF_PATH),
+            Some(proof_data_dir.join(sp1::SP1_PUB_INPUT_PATH).as_path()),
+            &args.aligned,
+            ProvingSystemId::SP1,
+        )
+        .await
+        .map_err(|e| {
+            error!("Proof not submitted to Aligned");
+            io::Error::other(e.to_string())
+        })?;
+        info!("SP1 proof submitted and verified on Aligned");
+        proof_output.aligned = Some(submission);
+    }
+    Ok(proof_output)
+}
+
+async fn prove_risc0(args: &ProofArgs, output_format: OutputFormat) -> io::Result<ProofOutput> {
+    info!("Proving with Risc0, program in: {}", args.guest_path);
+
+    // Perform sanitation checks on directory
+    if !utils::validate_directory_structure(&args.guest_path) {
+        return Err(io::Error::other(
+            "zkRust directory structure incorrect please consult the README",
+        ));
+    }
+    let proof_data_dir = proof_data_dir(args)?;
+    if !proof_data_dir.exists() {
+        std::fs::create_dir_all(&proof_data_dir).unwrap_or(info!(
+            "Saving generated proofs to: {:?}",
+            &args.proof_data_directory_path
+        ));
+    }
+    lint_before_build(&args.guest_path);
+    let home_dir = zkrust_home()?;
+    let Ok(current_dir) = std::env::current_dir() else {
+        return Err(io::Error::other("Failed to locate current directory"));
+    };
+    let input_file = resolve_input(args, &home_dir)?;
+    let host_input = match input_file {
+        Some(_) => HostInput::File,
+        None => HostInput::Function,
+    };
+    let source_maps = risc0::prepare(&PathBuf::from(&args.guest_path), &home_dir, host_input)?;
+
+    if !args.precompiles {
+        precompiles::suggest(Backend::Risc0, &PathBuf::from(&args.guest_path), &home_dir);
+    }
+    let _precompiles = args
+        .precompiles
+        .then(|| Backend::Risc0.apply_precompiles(&PathBuf::from(&args.guest_path), &home_dir))
+        .transpose()?;
+
+    remove_stale_artifact(&proof_data_dir.join(risc0::PUBLIC_INPUTS_FILE_PATH))?;
+    let mut envs = host_envs(args, input_file.as_ref())?;
+    envs.extend(
+        ProgramCache::new(&home_dir, Backend::Risc0, Path::new(&args.guest_path))?
+            .prepare(&home_dir)?,
+    );
+    build_workspace(
+        Backend::Risc0,
+        &home_dir,
+        &envs,
+        &source_maps,
+        output_format,
+    )?;
+    let status = Backend::Risc0.generate_proof(
+        &home_dir,
+        &current_dir,
+        output_format.child_stdout(),
+        &envs,
+        &args.host_args,
+    );
+    Backend::Risc0.clear_host(&home_dir).inspect_err(|_| {
+        error!("Failed to clear Risc0 host file");
+    })?;
+    if !status?.success() {
+        return Err(io::Error::other("Risc0 proof generation failed"));
+    }
+    info!("Risc0 proof and Image ID generated");
+
+    let mut proof_output = ProofOutput::from_artifacts(
+        "risc0",
+        ProofArtifacts::new(
+            proof_data_dir.join(risc0::PROOF_FILE_PATH),
+            proof_data_dir.join(risc0::IMAGE_ID_FILE_PATH),
+            proof_data_dir.join(risc0::PUBLIC_INPUT_FILE_PATH),
+            proof_data_dir.join(risc0::PUBLIC_INPUTS_FILE_PATH),
+        ),
+        &proof_data_dir.join(risc0::IMAGE_ID_FILE_PATH),
+        &proof_data_dir.join(risc0::CYCLES_FILE_PATH),
+    )?;
+
+    // Submit to aligned
+    if args.submit_to_aligned {
+        let submission = submit_proof_to_aligned(
+            &proof_data_dir.join(risc0::PROOF_FILE_PATH),
+            &proof_data_dir.join(risc0::IMAGE_ID_FILE_PATH),
+            Some(proof_data_dir.join(risc0::PUBLIC_INPUT_FILE_PATH).as_path()),
+            &args.aligned,
+            ProvingSystemId::Risc0,
+        )
+        .await
+        .map_err(|e| {
+            error!("Error submitting proofs to Aligned: {:?}", e);
+            io::Error::other(e.to_string())
+        })?;
+
+        info!("Risc0 proof submitted and verified on Aligned");
+        proof_output.aligned = Some(submission);
+    }
+    Ok(proof_output)
+}
+
+// The guest is built once and the host proves every input file with the same proving key or prover.
+fn prove_batch(
+    args: &ProveArgs,
+    backend: Backend,
+    inputs_dir: &Path,
+    output_format: OutputFormat,
+) -> io::Result<BatchOutput> {
+    let proof_args = &args.proof;
+    info!(
+        "Proving with {:?} over the inputs in {}, program in: {}",
+        backend,
+        inputs_dir.display(),
+        proof_args.guest_path
+    );
+    if proof_args.submit_to_aligned {
+        return Err(io::Error::other(
+            "--submit-to-aligned is not supported with --inputs-dir",
+        ));
+    }
+    if let Some(input) = &proof_args.input {
+        warn!(
+            "`input` of zkrust.toml ({}) is ignored, --inputs-dir proves the files of {}",
+            input,
+            inputs_dir.display()
+        );
+    }
+    if !utils::validate_directory_structure(&proof_args.guest_path) {
+        return Err(io::Error::other(
+            "zkRust directory structure invalid please consult the README",
+        ));
+    }
+    lint_before_build(&proof_args.guest_path);
+    let home_dir = zkrust_home()?;
+    let Ok(current_dir) = std::env::current_dir() else {
+        return Err(io::Error::other("Failed to locate current directory"));
+    };
+
+    let guest_path = PathBuf::from(&proof_args.guest_path);
+    let files = batch::input_files(inputs_dir)?;
+    for file in &files {
+        let input_file = InputFile::resolve(
+            &file.display().to_string(),
+            proof_args.input_format,
+            &home_dir,
+        )?;
+        input_file.validate(&guest_path)?;
+    }
+    let source_maps = backend.prepare(&guest_path, &home_dir, HostInput::File)?;
+    if !proof_args.precompiles {
+        precompiles::suggest(backend, &guest_path, &home_dir);
+    }
+    let _precompiles = proof_args
+        .precompiles
+        .then(|| backend.apply_precompiles(&guest_path, &home_dir))
+        .transpose()?;
+
+    // Proofs of a previous batch must not be reported as proofs of this one
+    let proof_data_dir = proof_data_dir(proof_args)?;
+    for file in &files {
+        let proof_dir = batch::proof_dir(&proof_data_dir, backend, file);
+        if proof_dir.exists() {
+            std::fs::remove_dir_all(proof_dir)?;
+        }
+    }
+    let mut envs = host_envs(proof_args, None)?;
+    envs.extend(
+        ProgramCache::new(&home_dir, backend, Path::new(&proof_args.guest_path))?
+            .prepare(&home_dir)?,
+    );
+    envs.push(batch::inputs_env(&files)?);
+    if let Some(format) = proof_args.input_format {
+        envs.push((zkRust::input::INPUT_FORMAT_ENV, format.as_str().to_string()));
+    }
+    build_workspace(backend, &home_dir, &envs, &source_maps, output_format)?;
+    let status = backend.generate_proof(
+        &home_dir,
+        &current_dir,
+        output_format.child_stdout(),
+        &envs,
+        &proof_args.host_args,
+    )?;
+
+    let batch_output = batch::collect(backend, &proof_data_dir, &files);
+    backend.clear_host(&home_dir)?;
+    batch::log_summary(&batch_output);
+    // Failed inputs are reported with the others, the host only fails alone when it crashed
+    if !status.success() && batch_output.failed == 0 {
+        return Err(io::Error::other(format!("The host failed with {}", status)));
+    }
+    Ok(batch_output)
+}
+
+// The program is executed without proving, the backend executor writes the raw profile.
+fn profile(
+    args: &ProfileArgs,
+    backend: Backend,
+    output_format: OutputFormat,
+) -> io::Result<ProfileOutput> {
+    let proof_args = &args.proof;
+    info!(
+        "Profiling with {:?}, program in: {}",
+        backend, proof_args.guest_path
+    );
+    if proof_args.submit_to_aligned {
+        return Err(io::Error::other(
+            "--submit-to-aligned is not supported by profile, no proof is generated",
+        ));
+    }
+    if !utils::validate_directory_structure(&proof_args.guest_path) {
+        return Err(io::Error::other(
+            "zkRust directory structure invalid please consult the README",
+        ));
+    }
+    lint_before_build(&proof_args.guest_path);
+    let home_dir = zkrust_home()?;
+    let Ok(current_dir) = std::env::current_dir() else {
+        return Err(io::Error::other("Failed to locate current directory"));
+    };
+    let input_file = resolve_input(proof_args, &home_dir)?;
+    let host_input = match input_file {
+        Some(_) => HostInput::File,
+        None => HostInput::Function,
+    };
+    let guest_path = PathBuf::from(&proof_args.guest_path);
+    let source_maps = backend.prepare(&guest_path, &home_dir, host_input)?;
+    let _precompiles = proof_args
+        .precompiles
+        .then(|| backend.apply_precompiles(&guest_path, &home_dir))
+        .transpose()?;
+
+    let proof_data_dir = proof_data_dir(proof_args)?;
+    let (raw_profile, profile_path) = match backend {
+        Backend::Sp1 => (
+            proof_data_dir.join(profile::SP1_TRACE_PATH),
+            proof_data_dir.join(profile::SP1_FOLDED_PATH),
+        ),
+        Backend::Risc0 => (
+            proof_data_dir.join(profile::RISC0_PPROF_PATH),
+            proof_data_dir.join(profile::RISC0_PPROF_PATH),
+        ),
+    };
+    std::fs::create_dir_all(proof_data_dir.join(backend.name()))?;
+    remove_stale_artifact(&raw_profile)?;
+    let mut envs = host_envs(proof_args, input_file.as_ref())?;
+    envs.extend(
+        ProgramCache::new(&home_dir, backend, Path::new(&proof_args.guest_path))?
+            .prepare(&home_dir)?,
+    );
+    envs.extend(profile::profile_envs(backend, &proof_data_dir));
+    build_workspace(backend, &home_dir, &envs, &source_maps, output_format)?;
+    let status = backend.generate_proof(
+        &home_dir,
+        &current_dir,
+        output_format.child_stdout(),
+        &envs,
+        &proof_args.host_args,
+    )?;
+    if !status.success() || !raw_profile.exists() {
+        backend.clear_host(&home_dir)?;
+        return Err(io::Error::other(format!("{:?} profiling failed", backend)));
+    }
+    backend.clear_host(&home_dir)?;
+
+    let profile_output = match backend {
+        Backend::Sp1 => {
+            let top_functions = profile::fold_sp1_trace(
+                &home_dir.join(sp1::SP1_GUEST_ELF),
+                &raw_profile,
+                &profile_path,
+            )?;
+            // The trace holds one word per cycle, only the folded stacks are kept
+            std::fs::remove_file(&raw_profile)?;
+            profile::log_top_functions(&top_functions);
+            info!(
+                "Folded stacks written to {:?}, render them with `inferno-flamegraph` or `flamegraph.pl`",
+                profile_path
+            );
+            ProfileOutput {
+                backend,
+                profile: profile_path,
+                format: "folded",
+                top_functions,
             }
         }
+        Backend::Risc0 => {
+            info!(
+                "pprof profile written to {:?}, open it with `go tool pprof -http=127.0.0.1:8000`",
+                profile_path
+            );
+            ProfileOutput {
+                backend,
+                profile: profile_path,
+                format: "pprof",
+                top_functions: vec![],
+            }
+        }
+    };
+    Ok(profile_output)
+}
+
+// Executes the program without proving it and returns its cycle count.
+fn execute(
+    backend: Backend,
+    home_dir: &Path,
+    current_dir: &PathBuf,
+    envs: &[(&str, String)],
+    proof_args: &ProofArgs,
+    source_maps: &SourceMaps,
+    output_format: OutputFormat,
+) -> io::Result<Option<u64>> {
+    let cycles_path = proof_data_dir(proof_args)?.join(match backend {
+        Backend::Sp1 => sp1::SP1_EXECUTE_CYCLES_PATH,
+        Backend::Risc0 => risc0::EXECUTE_CYCLES_FILE_PATH,
+    });
+    remove_stale_artifact(&cycles_path)?;
+    let mut envs = envs.to_vec();
+    envs.extend(
+        ProgramCache::new(home_dir, backend, Path::new(&proof_args.guest_path))?
+            .prepare(home_dir)?,
+    );
+    envs.push((utils::EXECUTE_ENV, "1".to_string()));
+    build_workspace(backend, home_dir, &envs, source_maps, output_format)?;
+    let status = backend.generate_proof(
+        home_dir,
+        current_dir,
+        output_format.child_stdout(),
+        &envs,
+        &proof_args.host_args,
+    )?;
+    if !status.success() {
+        return Ok(None);
     }
+    let cycles = std::fs::read_to_string(&cycles_path)?;
+    cycles
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|e| io::Error::other(format!("Invalid cycle count in {:?}: {}", cycles_path, e)))
+}
+
+// The program is executed once as is and once with the precompile patches, the guest is built
+// twice as the patched dependencies differ.
+fn estimate_precompiles(
+    args: &PrecompilesArgs,
+    backend: Backend,
+    output_format: OutputFormat,
+) -> io::Result<PrecompilesOutput> {
+    let proof_args = &args.proof;
+    info!(
+        "Estimating {:?} precompiles, program in: {}",
+        backend, proof_args.guest_path
+    );
+    if !utils::validate_directory_structure(&proof_args.guest_path) {
+        return Err(io::Error::other(
+            "zkRust directory structure invalid please consult the README",
+        ));
+    }
+    lint_before_build(&proof_args.guest_path);
+    let home_dir = zkrust_home()?;
+    let Ok(current_dir) = std::env::current_dir() else {
+        return Err(io::Error::other("Failed to locate current directory"));
+    };
+    let input_file = resolve_input(proof_args, &home_dir)?;
+    let host_input = match input_file {
+        Some(_) => HostInput::File,
+        None => HostInput::Function,
+    };
+    let guest_path = PathBuf::from(&proof_args.guest_path);
+    let source_maps = backend.prepare(&guest_path, &home_dir, host_input)?;
+    let crates = precompiles::accelerated_crates(backend, &guest_path, &home_dir)?;
+    if crates.is_empty() {
+        backend.clear_host(&home_dir)?;
+        return Err(io::Error::other(format!(
+            "No dependency of the program is accelerated by {:?} precompiles",
+            backend
+        )));
+    }
+
+    let envs = host_envs(proof_args, input_file.as_ref())?;
+    info!("Executing without precompiles");
+    let without = execute(
+        backend,
+        &home_dir,
+        &current_dir,
+        &envs,
+        proof_args,
+        &source_maps,
+        output_format,
+    )?;
+    let with = match without {
+        Some(_) => {
+            info!("Executing with precompiles");
+            let _precompiles = backend.apply_precompiles(&guest_path, &home_dir)?;
+            execute(
+                backend,
+                &home_dir,
+                &current_dir,
+                &envs,
+                proof_args,
+                &source_maps,
+                output_format,
+            )?
+        }
+        None => None,
+    };
+    let (Some(cycles_without_precompiles), Some(cycles_with_precompiles)) = (without, with) else {
+        backend.clear_host(&home_dir)?;
+        return Err(io::Error::other(format!("{:?} execution failed", backend)));
+    };
+    backend.clear_host(&home_dir)?;
+    Ok(PrecompilesOutput {
+        backend,
+        crates,
+        cycles_without_precompiles,
+        cycles_with_precompiles,
+    })
 }
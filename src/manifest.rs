@@ -0,0 +1,286 @@
+use log::info;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use crate::Backend;
+
+/// Guest manifest settings of `[package.metadata.zkrust]` in the project `Cargo.toml`. The keys
+/// of `[package.metadata.zkrust.sp1]` and `[package.metadata.zkrust.risc0]` override the shared
+/// ones for their backend.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectMetadata {
+    #[serde(flatten)]
+    pub shared: GuestManifest,
+    pub sp1: GuestManifest,
+    pub risc0: GuestManifest,
+}
+
+/// Additions to the generated guest manifest, the host manifest is left as is.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GuestManifest {
+    /// `[patch.crates-io]` entries, e.g. a fork of a crate for the zkVM
+    pub patches: toml::Table,
+    /// Dependencies only built into the guest, replacing project dependencies of the same name
+    pub guest_dependencies: toml::Table,
+    /// Features of dependencies only enabled in the guest
+    pub guest_features: BTreeMap<String, Vec<String>>,
+}
+
+impl GuestManifest {
+    fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+            && self.guest_dependencies.is_empty()
+            && self.guest_features.is_empty()
+    }
+
+    // Backend entries replace shared entries of the same name.
+    fn merge(mut self, backend: GuestManifest) -> GuestManifest {
+        self.patches.extend(backend.patches);
+        self.guest_dependencies.extend(backend.guest_dependencies);
+        self.guest_features.extend(backend.guest_features);
+        self
+    }
+}
+
+impl ProjectMetadata {
+    /// Reads `[package.metadata.zkrust]` from the manifest of the project at `guest_path`.
+    pub fn read(guest_path: &Path) -> io::Result<ProjectMetadata> {
+        let manifest = read_manifest(&guest_path.join("Cargo.toml"))?;
+        let Some(metadata) = manifest
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("zkrust"))
+        else {
+            return Ok(ProjectMetadata::default());
+        };
+        metadata
+            .clone()
+            .try_into()
+            .map_err(|e| io::Error::other(format!("Invalid [package.metadata.zkrust]: {}", e)))
+    }
+
+    /// Settings of the guest manifest of `backend`.
+    pub fn guest_manifest(self, backend: Backend) -> GuestManifest {
+        match backend {
+            Backend::Sp1 => self.shared.merge(self.sp1),
+            Backend::Risc0 => self.shared.merge(self.risc0),
+        }
+    }
+}
+
+/// Merges the `[package.metadata.zkrust]` settings of the project into the generated guest
+/// manifest of `backend`.
+pub fn apply_project_metadata(
+    guest_path: &Path,
+    backend: Backend,
+    guest_toml_path: &Path,
+) -> io::Result<()> {
+    let guest = ProjectMetadata::read(guest_path)?.guest_manifest(backend);
+    if guest.is_empty() {
+        return Ok(());
+    }
+    let mut manifest = read_manifest(guest_toml_path)?;
+    // Sections after `[dependencies]` of the project are copied along with the dependencies
+    if let Some(package) = manifest
+        .get_mut("package")
+        .and_then(|package| package.as_table_mut())
+    {
+        if let Some(metadata) = package
+            .get_mut("metadata")
+            .and_then(|metadata| metadata.as_table_mut())
+        {
+            metadata.remove("zkrust");
+            if metadata.is_empty() {
+                package.remove("metadata");
+            }
+        }
+    }
+
+    let dependencies = table_entry(&mut manifest, "dependencies")?;
+    for (name, dependency) in guest.guest_dependencies {
+        info!("Adding guest dependency `{}`", name);
+        dependencies.insert(name, dependency);
+    }
+    for (name, features) in guest.guest_features {
+        let Some(dependency) = dependencies.get_mut(&name) else {
+            return Err(io::Error::other(format!(
+                "guest-features of [package.metadata.zkrust] names `{}`, which is not a dependency",
+                name
+            )));
+        };
+        // `name = "1.0"` becomes `name = { version = "1.0", features = [...] }`
+        if let toml::Value::String(version) = dependency {
+            let mut table = toml::Table::new();
+            table.insert("version".to_string(), toml::Value::String(version.clone()));
+            *dependency = toml::Value::Table(table);
+        }
+        let Some(table) = dependency.as_table_mut() else {
+            return Err(io::Error::other(format!(
+                "Dependency `{}` of the guest manifest is not a table",
+                name
+            )));
+        };
+        let enabled = table
+            .entry("features")
+            .or_insert_with(|| toml::Value::Array(vec![]));
+        let Some(enabled) = enabled.as_array_mut() else {
+            return Err(io::Error::other(format!(
+                "Features of dependency `{}` must be an array",
+                name
+            )));
+        };
+        for feature in features {
+            let feature = toml::Value::String(feature);
+            if !enabled.contains(&feature) {
+                enabled.push(feature);
+            }
+        }
+    }
+
+    add_patches(&mut manifest, guest.patches, true)?;
+    write_manifest(guest_toml_path, &manifest)
+}
+
+/// Adds `patches` to `[patch.crates-io]`. Entries already patched are kept unless `replace` is set.
+/// Returns the names of the patches added.
+pub fn add_patches(
+    manifest: &mut toml::Table,
+    patches: toml::Table,
+    replace: bool,
+) -> io::Result<Vec<String>> {
+    if patches.is_empty() {
+        return Ok(vec![]);
+    }
+    let patch = table_entry(manifest, "patch")?;
+    let crates_io = table_entry(patch, "crates-io")?;
+    let mut added = vec![];
+    for (name, source) in patches {
+        if replace || !crates_io.contains_key(&name) {
+            crates_io.insert(name.clone(), source);
+            added.push(name);
+        }
+    }
+    Ok(added)
+}
+
+pub fn read_manifest(toml_path: &Path) -> io::Result<toml::Table> {
+    fs::read_to_string(toml_path)?
+        .parse::<toml::Table>()
+        .map_err(|e| io::Error::other(format!("Failed to parse {:?}: {}", toml_path, e)))
+}
+
+pub fn write_manifest(toml_path: &Path, manifest: &toml::Table) -> io::Result<()> {
+    fs::write(
+        toml_path,
+        toml::to_string(manifest).map_err(io::Error::other)?,
+    )
+}
+
+fn table_entry<'a>(table: &'a mut toml::Table, key: &str) -> io::Result<&'a mut toml::Table> {
+    table
+        .entry(key)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| io::Error::other(format!("`{}` of the guest manifest must be a table", key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT_MANIFEST: &str = r#"[package]
+name = "program"
+version = "0.1.0"
+
+[dependencies]
+sha2 = "0.10.8"
+
+[package.metadata.zkrust]
+guest-features = { sha2 = ["asm"] }
+guest-dependencies = { getrandom = { version = "0.2", features = ["custom"] } }
+
+[package.metadata.zkrust.patches]
+sha2 = { git = "https://github.com/project/sha2" }
+k256 = { git = "https://github.com/project/k256" }
+
+[package.metadata.zkrust.sp1.patches]
+k256 = { git = "https://github.com/sp1/k256" }
+"#;
+
+    // Generated guest manifest, with the dependencies and metadata copied from the project
+    const GUEST_MANIFEST: &str = r#"[package]
+name = "method"
+version = "0.1.0"
+
+[dependencies]
+sha2 = "0.10.8"
+
+[package.metadata.zkrust]
+guest-features = { sha2 = ["asm"] }
+
+[patch.crates-io]
+sha2 = { git = "https://github.com/template/sha2" }
+"#;
+
+    fn apply(backend: Backend) -> toml::Table {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), PROJECT_MANIFEST).unwrap();
+        let guest_toml_path = dir.path().join("guest.toml");
+        fs::write(&guest_toml_path, GUEST_MANIFEST).unwrap();
+        apply_project_metadata(dir.path(), backend, &guest_toml_path).unwrap();
+        read_manifest(&guest_toml_path).unwrap()
+    }
+
+    fn git(manifest: &toml::Table, name: &str) -> String {
+        manifest["patch"]["crates-io"][name]["git"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn guest_features_upgrade_version_strings_to_tables() {
+        let manifest = apply(Backend::Risc0);
+        let sha2 = manifest["dependencies"]["sha2"].as_table().unwrap();
+        assert_eq!(sha2["version"].as_str(), Some("0.10.8"));
+        assert_eq!(
+            sha2["features"].as_array().unwrap(),
+            &vec![toml::Value::String("asm".to_string())]
+        );
+        assert_eq!(
+            manifest["dependencies"]["getrandom"]["version"].as_str(),
+            Some("0.2")
+        );
+        // The metadata of the project is not part of the guest manifest
+        assert!(manifest["package"].get("metadata").is_none());
+    }
+
+    #[test]
+    fn backend_patches_override_shared_and_generated_patches() {
+        let sp1 = apply(Backend::Sp1);
+        assert_eq!(git(&sp1, "sha2"), "https://github.com/project/sha2");
+        assert_eq!(git(&sp1, "k256"), "https://github.com/sp1/k256");
+        let risc0 = apply(Backend::Risc0);
+        assert_eq!(git(&risc0, "k256"), "https://github.com/project/k256");
+    }
+
+    #[test]
+    fn precompile_patches_do_not_replace_project_patches() {
+        let mut manifest = apply(Backend::Sp1);
+        let precompiles: toml::Table = r#"
+sha2 = { git = "https://github.com/precompiles/sha2" }
+sha3 = { git = "https://github.com/precompiles/sha3" }
+"#
+        .parse()
+        .unwrap();
+        let added = add_patches(&mut manifest, precompiles, false).unwrap();
+        assert_eq!(added, vec!["sha3".to_string()]);
+        assert_eq!(git(&manifest, "sha2"), "https://github.com/project/sha2");
+        assert_eq!(
+            git(&manifest, "sha3"),
+            "https://github.com/precompiles/sha3"
+        );
+    }
+}
@@ -0,0 +1,477 @@
+use aligned_sdk::core::types::{
+    BatchInclusionData, ClientMessage, ProofInvalidReason, ProvingSystemId, ResponseMessage,
+    VerificationCommitmentBatch, VerificationData, VerificationDataCommitment,
+};
+use clap::Args;
+use ethers::types::{Address, U256};
+use ethers::utils::id;
+use futures_util::{SinkExt, StreamExt};
+use lambdaworks_crypto::merkle_tree::{merkle::MerkleTree, proof::Proof};
+use log::{error, info, warn};
+use risc0_zkvm::{sha::Digest, Receipt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sp1_sdk::{ProverClient, SP1ProofWithPublicValues};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Protocol version announced to clients, the one of the batcher aligned-sdk v0.9.2 talks to.
+const PROTOCOL_VERSION: u16 = 4;
+
+// Balance of every account in the payment service, so submitting never asks for a deposit
+const ACCOUNT_BALANCE_ETH: u64 = 100;
+const GAS_PRICE_WEI: u64 = 1_000_000_000;
+
+// Calls answered by the RPC endpoint, whatever the address of the contract called
+const USER_BALANCES: &str = "user_balances(address)";
+const USER_NONCES: &str = "user_nonces(address)";
+const VERIFY_BATCH_INCLUSION: &str =
+    "verifyBatchInclusion(bytes32,bytes32,bytes32,bytes20,bytes32,bytes,uint256)";
+
+#[derive(Args, Debug)]
+pub struct MockBatcherArgs {
+    #[clap(
+        long = "port",
+        default_value_t = 8080,
+        help = "Port of the batcher, submit with `--batcher-url ws://localhost:<port>`, 0 picks a free port"
+    )]
+    pub port: u16,
+    #[clap(
+        long = "rpc-port",
+        default_value_t = 8545,
+        help = "Port of the Ethereum RPC node, submit with `--rpc-url http://localhost:<rpc-port>`, 0 picks a free port"
+    )]
+    pub rpc_port: u16,
+    #[clap(
+        long = "chain-id",
+        default_value_t = 31337,
+        help = "Chain ID of the RPC node"
+    )]
+    pub chain_id: u64,
+    #[clap(
+        long = "batch-interval",
+        default_value_t = 1000,
+        help = "Milliseconds between the batches of the proofs received"
+    )]
+    pub batch_interval: u64,
+    #[clap(
+        long = "skip-verification",
+        help = "Accept proofs without verifying them"
+    )]
+    pub skip_verification: bool,
+}
+
+struct PendingProof {
+    commitment: VerificationDataCommitment,
+    responses: mpsc::UnboundedSender<ResponseMessage>,
+}
+
+#[derive(Default)]
+struct MockState {
+    // Proofs of the next batch
+    pending: Vec<PendingProof>,
+    // Nonce of the next proof of each submitter
+    nonces: HashMap<Address, U256>,
+    // Merkle roots of the batches closed so far
+    batch_roots: HashSet<[u8; 32]>,
+}
+
+type SharedState = Arc<Mutex<MockState>>;
+
+/// Serves the batcher websocket and the RPC node until the process is stopped. Proofs are
+/// verified with the zkVM SDKs zkRust proves with, instead of the Aligned operators.
+pub async fn run(args: &MockBatcherArgs) -> io::Result<()> {
+    MockBatcher::bind(args).await?.serve().await
+}
+
+/// Batcher and RPC node bound to their ports, `0` binds any free port.
+pub struct MockBatcher {
+    batcher: TcpListener,
+    rpc: TcpListener,
+    chain_id: u64,
+    batch_interval: Duration,
+    skip_verification: bool,
+}
+
+impl MockBatcher {
+    pub async fn bind(args: &MockBatcherArgs) -> io::Result<Self> {
+        let mock = MockBatcher {
+            batcher: TcpListener::bind(("127.0.0.1", args.port)).await?,
+            rpc: TcpListener::bind(("127.0.0.1", args.rpc_port)).await?,
+            chain_id: args.chain_id,
+            batch_interval: Duration::from_millis(args.batch_interval),
+            skip_verification: args.skip_verification,
+        };
+        info!("Mock batcher listening on {}", mock.batcher_url()?);
+        info!(
+            "Mock RPC node of chain {} listening on {}",
+            mock.chain_id,
+            mock.rpc_url()?
+        );
+        Ok(mock)
+    }
+
+    /// `--batcher-url` of the bound batcher.
+    pub fn batcher_url(&self) -> io::Result<String> {
+        Ok(format!("ws://{}", self.batcher.local_addr()?))
+    }
+
+    /// `--rpc-url` of the bound RPC node.
+    pub fn rpc_url(&self) -> io::Result<String> {
+        Ok(format!("http://{}", self.rpc.local_addr()?))
+    }
+
+    /// Answers the clients until the process is stopped, or the future dropped.
+    pub async fn serve(self) -> io::Result<()> {
+        let state = SharedState::default();
+        tokio::spawn(serve_rpc(self.rpc, state.clone(), self.chain_id));
+        tokio::spawn(close_batches(state.clone(), self.batch_interval));
+        loop {
+            let (stream, peer) = self.batcher.accept().await?;
+            let state = state.clone();
+            let skip_verification = self.skip_verification;
+            tokio::spawn(async move {
+                if let Err(e) = handle_client(stream, state, skip_verification).await {
+                    warn!("Connection of {} closed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+async fn handle_client(
+    stream: TcpStream,
+    state: SharedState,
+    skip_verification: bool,
+) -> io::Result<()> {
+    let ws = tokio_tungstenite::accept_async(stream)
+        .await
+        .map_err(io::Error::other)?;
+    let (mut write, mut read) = ws.split();
+    let (responses, mut outgoing) = mpsc::unbounded_channel();
+    let _ = responses.send(ResponseMessage::ProtocolVersion(PROTOCOL_VERSION));
+
+    // Inclusion proofs are sent when the batch closes, while the client may still be sending proofs
+    let writer = tokio::spawn(async move {
+        while let Some(response) = outgoing.recv().await {
+            write
+                .send(Message::binary(cbor(&response)?))
+                .await
+                .map_err(io::Error::other)?;
+        }
+        Ok::<(), io::Error>(())
+    });
+
+    while let Some(message) = read.next().await {
+        let message = message.map_err(io::Error::other)?;
+        if message.is_close() {
+            break;
+        }
+        if !message.is_binary() {
+            continue;
+        }
+        match receive_proof(&message.into_data(), &state, skip_verification).await {
+            Ok(commitment) => state.lock().unwrap().pending.push(PendingProof {
+                commitment,
+                responses: responses.clone(),
+            }),
+            Err(response) => {
+                let _ = responses.send(response);
+            }
+        }
+    }
+    drop(responses);
+    writer.await.map_err(io::Error::other)?
+}
+
+// Checks a proof sent by a client, returns its commitment or the response rejecting it.
+async fn receive_proof(
+    bytes: &[u8],
+    state: &SharedState,
+    skip_verification: bool,
+) -> Result<VerificationDataCommitment, ResponseMessage> {
+    let message: ClientMessage = ciborium::from_reader(bytes)
+        .map_err(|e| ResponseMessage::Error(format!("Invalid message: {}", e)))?;
+    let sender = message
+        .verify_signature()
+        .map_err(|e| ResponseMessage::Error(format!("Invalid signature: {:?}", e)))?;
+    let nonce = U256::from_big_endian(&message.verification_data.nonce);
+    let verification_data = message.verification_data.verification_data;
+
+    if !skip_verification {
+        let data = verification_data.clone();
+        let verified = tokio::task::spawn_blocking(move || verify(&data))
+            .await
+            .map_err(|e| ResponseMessage::Error(e.to_string()))?;
+        if let Err(e) = verified {
+            warn!(
+                "Rejected {:?} proof of {:?}: {}",
+                verification_data.proving_system, sender, e
+            );
+            return Err(ResponseMessage::InvalidProof(
+                ProofInvalidReason::RejectedProof,
+            ));
+        }
+    }
+
+    let mut state = state.lock().unwrap();
+    let expected = state.nonces.entry(sender).or_default();
+    if nonce != *expected {
+        return Err(ResponseMessage::Error(format!(
+            "Invalid nonce {} of {:?}, expected {}",
+            nonce, sender, expected
+        )));
+    }
+    *expected = nonce + 1;
+    info!(
+        "Accepted {:?} proof of {:?} with nonce {}",
+        verification_data.proving_system, sender, nonce
+    );
+    Ok(VerificationDataCommitment::from(&verification_data))
+}
+
+// Verifies the proofs of the zkVMs zkRust supports, as written by the generated hosts.
+fn verify(data: &VerificationData) -> Result<(), String> {
+    let program = data
+        .vm_program_code
+        .as_deref()
+        .ok_or("Missing program of the proof")?;
+    match &data.proving_system {
+        ProvingSystemId::SP1 => {
+            let proof: SP1ProofWithPublicValues =
+                bincode::deserialize(&data.proof).map_err(|e| e.to_string())?;
+            let client = ProverClient::new();
+            let (_, vk) = client.setup(program);
+            client.verify(&proof, &vk).map_err(|e| e.to_string())
+        }
+        ProvingSystemId::Risc0 => {
+            let receipt: Receipt = bincode::deserialize(&data.proof).map_err(|e| e.to_string())?;
+            let image_id =
+                Digest::try_from(program).map_err(|_| "Image ID is not 32 bytes".to_string())?;
+            if data.pub_input.as_deref() != Some(receipt.journal.bytes.as_slice()) {
+                return Err("Public input does not match the journal".to_string());
+            }
+            receipt.verify(image_id).map_err(|e| e.to_string())
+        }
+        other => Err(format!("{:?} proofs are not supported", other)),
+    }
+}
+
+// Closes a batch of the pending proofs every `interval` and sends each client the inclusion
+// proofs of its proofs in the merkle tree of the batch.
+async fn close_batches(state: SharedState, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let mut state = state.lock().unwrap();
+        if state.pending.is_empty() {
+            continue;
+        }
+        let batch: Vec<PendingProof> = state.pending.drain(..).collect();
+        let commitments: Vec<VerificationDataCommitment> =
+            batch.iter().map(|proof| proof.commitment.clone()).collect();
+        let Some(tree) = MerkleTree::<VerificationCommitmentBatch>::build(&commitments) else {
+            error!("Failed to build the merkle tree of {} proofs", batch.len());
+            for proof in batch {
+                let _ = proof.responses.send(ResponseMessage::Error(
+                    "Failed to build the merkle tree of the batch".to_string(),
+                ));
+            }
+            continue;
+        };
+        state.batch_roots.insert(tree.root);
+        info!(
+            "Batch 0x{} of {} proofs verified",
+            hex::encode(tree.root),
+            batch.len()
+        );
+        for (index, proof) in batch.into_iter().enumerate() {
+            let _ = proof.responses.send(ResponseMessage::BatchInclusionData(
+                BatchInclusionData::new(index, &tree),
+            ));
+        }
+    }
+}
+
+async fn serve_rpc(listener: TcpListener, state: SharedState, chain_id: u64) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_rpc(stream, state, chain_id).await {
+                        warn!("RPC connection closed: {}", e);
+                    }
+                });
+            }
+            Err(e) => error!("Failed to accept an RPC connection: {}", e),
+        }
+    }
+}
+
+// HTTP/1.1 with one JSON-RPC request, or batch of requests, per message on a kept alive connection.
+async fn handle_rpc(stream: TcpStream, state: SharedState, chain_id: u64) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let mut content_length = 0;
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await?;
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().map_err(io::Error::other)?;
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        stream.read_exact(&mut body).await?;
+
+        let response = match serde_json::from_slice(&body)? {
+            Value::Array(requests) => Value::Array(
+                requests
+                    .iter()
+                    .map(|request| rpc_response(request, &state, chain_id))
+                    .collect(),
+            ),
+            request => rpc_response(&request, &state, chain_id),
+        }
+        .to_string();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            response.len()
+        );
+        stream.get_mut().write_all(head.as_bytes()).await?;
+        stream.get_mut().write_all(response.as_bytes()).await?;
+    }
+}
+
+fn rpc_response(request: &Value, state: &SharedState, chain_id: u64) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let result = match method {
+        "eth_chainId" => Ok(json!(format!("{:#x}", chain_id))),
+        "net_version" => Ok(json!(chain_id.to_string())),
+        "eth_gasPrice" => Ok(json!(format!("{:#x}", GAS_PRICE_WEI))),
+        "eth_blockNumber" => Ok(json!(format!(
+            "{:#x}",
+            state.lock().unwrap().batch_roots.len()
+        ))),
+        "eth_call" => eth_call(request.get("params").unwrap_or(&Value::Null), state),
+        _ => Err(format!("{} is not supported by the mock RPC node", method)),
+    };
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(message) => {
+            json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32000, "message": message } })
+        }
+    }
+}
+
+// Answers the calls of zkRust and aligned-sdk to the batcher payment service and the service
+// manager: every account is funded and a batch is verified once it was closed.
+fn eth_call(params: &Value, state: &SharedState) -> Result<Value, String> {
+    let data = params
+        .get(0)
+        .and_then(|call| call.get("data").or_else(|| call.get("input")))
+        .and_then(Value::as_str)
+        .ok_or("eth_call without data")?;
+    let data = hex::decode(data.trim_start_matches("0x")).map_err(|e| e.to_string())?;
+    if data.len() < 4 {
+        return Err("eth_call without a function selector".to_string());
+    }
+    let (selector, arguments) = data.split_at(4);
+
+    let state = state.lock().unwrap();
+    let result = if selector == id(USER_BALANCES) {
+        U256::exp10(18) * ACCOUNT_BALANCE_ETH
+    } else if selector == id(USER_NONCES) {
+        let account = Address::from_slice(&abi_word(arguments, 0)?[12..]);
+        state.nonces.get(&account).copied().unwrap_or_default()
+    } else if selector == id(VERIFY_BATCH_INCLUSION) {
+        U256::from(verify_batch_inclusion(&state, arguments)? as u8)
+    } else {
+        return Err(format!(
+            "Function 0x{} is not supported by the mock RPC node",
+            hex::encode(selector)
+        ));
+    };
+    let mut encoded = [0u8; 32];
+    result.to_big_endian(&mut encoded);
+    Ok(json!(format!("0x{}", hex::encode(encoded))))
+}
+
+// Checks the proof is in a batch the mock closed, as the service manager does: the leaf of its
+// commitments must hash up to the merkle root of the batch through the merkle path.
+fn verify_batch_inclusion(state: &MockState, arguments: &[u8]) -> Result<bool, String> {
+    let batch_merkle_root = abi_word(arguments, 4)?;
+    if !state.batch_roots.contains(&batch_merkle_root) {
+        return Ok(false);
+    }
+    let commitment = VerificationDataCommitment {
+        proof_commitment: abi_word(arguments, 0)?,
+        pub_input_commitment: abi_word(arguments, 1)?,
+        proving_system_aux_data_commitment: abi_word(arguments, 2)?,
+        // bytes20 is left aligned in its word
+        proof_generator_addr: abi_word(arguments, 3)?[..20]
+            .try_into()
+            .map_err(|_| "Invalid proof generator address".to_string())?,
+    };
+    let merkle_proof = arguments
+        .get(abi_usize(arguments, 5)?..)
+        .ok_or("Invalid merkle proof offset")?;
+    let merkle_proof_length = abi_usize(merkle_proof, 0)?;
+    let merkle_proof = merkle_proof
+        .get(32..)
+        .and_then(|bytes| bytes.get(..merkle_proof_length))
+        .filter(|bytes| bytes.len() % 32 == 0)
+        .ok_or("Invalid merkle proof")?;
+    let proof = Proof {
+        merkle_path: merkle_proof
+            .chunks_exact(32)
+            .map(|node| node.try_into().unwrap())
+            .collect(),
+    };
+    let index = abi_usize(arguments, 6)?;
+    Ok(proof.verify::<VerificationCommitmentBatch>(&batch_merkle_root, index, &commitment))
+}
+
+// Word `index` of ABI encoded call arguments.
+fn abi_word(arguments: &[u8], index: usize) -> Result<[u8; 32], String> {
+    arguments
+        .get(index * 32..(index + 1) * 32)
+        .and_then(|word| word.try_into().ok())
+        .ok_or_else(|| "Truncated eth_call arguments".to_string())
+}
+
+fn abi_usize(arguments: &[u8], index: usize) -> Result<usize, String> {
+    let value = U256::from_big_endian(&abi_word(arguments, index)?);
+    if value > U256::from(usize::MAX) {
+        return Err(format!("eth_call argument {} is out of range", value));
+    }
+    Ok(value.as_usize())
+}
+
+fn cbor<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    ciborium::into_writer(value, &mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes)
+}
@@ -0,0 +1,316 @@
+use aligned_sdk::core::errors::{AlignedError, SubmitError};
+use aligned_sdk::core::types::{AlignedVerificationData, Network, VerificationData};
+use aligned_sdk::sdk::{
+    deposit_to_aligned, get_balance_in_aligned, get_next_nonce, is_proof_verified, submit_multiple,
+};
+use clap::ValueEnum;
+use ethers::prelude::*;
+use log::info;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::NetworkArg;
+
+/// Replaced by the hex encoded batch merkle root, without `0x`, in explorer URL templates.
+pub const BATCH_MERKLE_ROOT_PLACEHOLDER: &str = "{batch_merkle_root}";
+
+const DEVNET_EXPLORER_URL: &str = "http://localhost:4000/batches/0x{batch_merkle_root}";
+const HOLESKY_EXPLORER_URL: &str =
+    "https://explorer.alignedlayer.com/batches/0x{batch_merkle_root}";
+const HOLESKY_STAGE_EXPLORER_URL: &str =
+    "https://stage.explorer.alignedlayer.com/batches/0x{batch_merkle_root}";
+
+// Delay between two checks of a batch waiting for its verification
+const VERIFICATION_INTERVAL: Duration = Duration::from_secs(10);
+
+abigen!(
+    BatcherPaymentService,
+    r#"[
+        function user_balances(address account) external view returns (uint256)
+        function user_nonces(address account) external view returns (uint256)
+    ]"#
+);
+
+abigen!(
+    AlignedLayerServiceManager,
+    r#"[
+        function verifyBatchInclusion(bytes32 proofCommitment, bytes32 pubInputCommitment, bytes32 provingSystemAuxDataCommitment, bytes20 proofGeneratorAddr, bytes32 batchMerkleRoot, bytes merkleProof, uint256 verificationDataBatchIndex) external view returns (bool)
+    ]"#
+);
+
+/// Aligned deployment declared in `[networks.<name>]` of zkrust.toml, e.g. a private deployment
+/// or a local test stack.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct NetworkProfile {
+    /// Default of `--rpc-url` on this network
+    pub rpc_url: Option<String>,
+    /// Default of `--batcher-url` on this network
+    pub batcher_url: Option<String>,
+    /// Checked against the chain ID of the RPC node before submitting
+    pub chain_id: Option<u64>,
+    pub batcher_payment_service: Address,
+    pub aligned_service_manager: Address,
+    /// Batch page, `{batch_merkle_root}` is replaced by the merkle root of the batch
+    pub explorer_url: Option<String>,
+    /// aligned-sdk network the messages to the batcher are built for
+    #[serde(default = "default_sdk_network")]
+    pub sdk_network: NetworkArg,
+}
+
+fn default_sdk_network() -> NetworkArg {
+    NetworkArg::Devnet
+}
+
+/// Network proofs are submitted to, selected by `--network`.
+#[derive(Debug, Clone)]
+pub enum AlignedNetwork {
+    /// Network with the contract addresses of aligned-sdk
+    Preset(NetworkArg),
+    Profile {
+        name: String,
+        profile: NetworkProfile,
+    },
+}
+
+/// Finds the network called `name`, profiles take precedence over the aligned-sdk networks.
+pub fn resolve(
+    name: &str,
+    profiles: &BTreeMap<String, NetworkProfile>,
+) -> io::Result<AlignedNetwork> {
+    if let Some(profile) = profiles.get(name) {
+        return Ok(AlignedNetwork::Profile {
+            name: name.to_string(),
+            profile: profile.clone(),
+        });
+    }
+    NetworkArg::from_str(name, true)
+        .map(AlignedNetwork::Preset)
+        .map_err(|_| {
+            let known: Vec<String> = NetworkArg::value_variants()
+                .iter()
+                .filter_map(|network| network.to_possible_value())
+                .map(|network| network.get_name().to_string())
+                .chain(profiles.keys().cloned())
+                .collect();
+            io::Error::other(format!(
+                "Unknown network `{}`, expected one of {} or a `[networks.{}]` profile in zkrust.toml",
+                name,
+                known.join(", "),
+                name
+            ))
+        })
+}
+
+impl AlignedNetwork {
+    /// Page of the batch on the explorer of the network, if it has one.
+    pub fn explorer_url(&self, batch_merkle_root: &str) -> Option<String> {
+        let template = match self {
+            AlignedNetwork::Preset(NetworkArg::Devnet) => Some(DEVNET_EXPLORER_URL),
+            AlignedNetwork::Preset(NetworkArg::Holesky) => Some(HOLESKY_EXPLORER_URL),
+            AlignedNetwork::Preset(NetworkArg::HoleskyStage) => Some(HOLESKY_STAGE_EXPLORER_URL),
+            AlignedNetwork::Profile { profile, .. } => profile.explorer_url.as_deref(),
+        }?;
+        Some(template.replace(BATCH_MERKLE_ROOT_PLACEHOLDER, batch_merkle_root))
+    }
+
+    /// Fails when the RPC node is not on the chain the profile was declared for.
+    pub fn check_chain_id(&self, chain_id: u64) -> Result<(), SubmitError> {
+        match self {
+            AlignedNetwork::Profile {
+                name,
+                profile:
+                    NetworkProfile {
+                        chain_id: Some(expected),
+                        ..
+                    },
+            } if *expected != chain_id => Err(SubmitError::GenericError(format!(
+                "The RPC node is on chain {}, network `{}` is declared on chain {}",
+                chain_id, name, expected
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Balance of `user` in the batcher payment service.
+    pub async fn balance(&self, user: Address, rpc_url: &str) -> Result<U256, SubmitError> {
+        match self {
+            AlignedNetwork::Preset(network) => {
+                get_balance_in_aligned(user, rpc_url, Network::from(*network))
+                    .await
+                    .map_err(|_| {
+                        SubmitError::GenericError(
+                            "Failed to retrieve user balance from Aligned".to_string(),
+                        )
+                    })
+            }
+            AlignedNetwork::Profile { profile, .. } => payment_service(profile, rpc_url)?
+                .user_balances(user)
+                .call()
+                .await
+                .map_err(|e| {
+                    SubmitError::GenericError(format!(
+                        "Failed to retrieve user balance from Aligned: {}",
+                        e
+                    ))
+                }),
+        }
+    }
+
+    /// Funds proof submission by sending `amount` to the batcher payment service.
+    pub async fn deposit(
+        &self,
+        amount: U256,
+        signer: SignerMiddleware<Provider<Http>, LocalWallet>,
+    ) -> Result<TransactionReceipt, SubmitError> {
+        let failed =
+            || SubmitError::GenericError("Failed to Deposit Funds into the Batcher".to_string());
+        match self {
+            AlignedNetwork::Preset(network) => {
+                deposit_to_aligned(amount, signer, Network::from(*network))
+                    .await
+                    .map_err(|_| failed())
+            }
+            AlignedNetwork::Profile { profile, .. } => {
+                let tx = TransactionRequest::new()
+                    .from(signer.address())
+                    .to(profile.batcher_payment_service)
+                    .value(amount);
+                signer
+                    .send_transaction(tx, None)
+                    .await
+                    .map_err(|_| failed())?
+                    .await
+                    .map_err(|_| failed())?
+                    .ok_or_else(failed)
+            }
+        }
+    }
+
+    /// Nonce of the next proof submitted by `address`.
+    pub async fn next_nonce(&self, rpc_url: &str, address: Address) -> Result<U256, AlignedError> {
+        match self {
+            AlignedNetwork::Preset(network) => {
+                Ok(get_next_nonce(rpc_url, address, Network::from(*network)).await?)
+            }
+            AlignedNetwork::Profile { profile, .. } => Ok(payment_service(profile, rpc_url)?
+                .user_nonces(address)
+                .call()
+                .await
+                .map_err(|e| SubmitError::GenericError(format!("Failed to get nonce: {}", e)))?),
+        }
+    }
+
+    /// Submits `verification_data` to the batcher in one call, numbered from `nonce`, and
+    /// returns the batch of each proof once the batcher answered.
+    pub async fn submit_multiple(
+        &self,
+        batcher_url: &str,
+        verification_data: &[VerificationData],
+        max_fees: &[U256],
+        wallet: LocalWallet,
+        nonce: U256,
+    ) -> Result<Vec<AlignedVerificationData>, AlignedError> {
+        let network = match self {
+            AlignedNetwork::Preset(network) => Network::from(*network),
+            AlignedNetwork::Profile { profile, .. } => profile.sdk_network.into(),
+        };
+        Ok(submit_multiple(
+            batcher_url,
+            network,
+            verification_data,
+            max_fees,
+            wallet,
+            nonce,
+        )
+        .await?)
+    }
+
+    /// Whether the batch of the proof was verified on chain.
+    pub async fn is_verified(
+        &self,
+        rpc_url: &str,
+        aligned_verification_data: &AlignedVerificationData,
+    ) -> Result<bool, AlignedError> {
+        let profile = match self {
+            AlignedNetwork::Preset(network) => {
+                return Ok(is_proof_verified(
+                    aligned_verification_data,
+                    Network::from(*network),
+                    rpc_url,
+                )
+                .await?)
+            }
+            AlignedNetwork::Profile { profile, .. } => profile,
+        };
+        // aligned-sdk checks the inclusion on its own service manager, profiles declare theirs
+        let service_manager = AlignedLayerServiceManager::new(
+            profile.aligned_service_manager,
+            provider(rpc_url)?.into(),
+        );
+        let commitment = &aligned_verification_data.verification_data_commitment;
+        let merkle_proof: Vec<u8> = aligned_verification_data
+            .batch_inclusion_proof
+            .merkle_path
+            .concat();
+        Ok(service_manager
+            .verify_batch_inclusion(
+                commitment.proof_commitment,
+                commitment.pub_input_commitment,
+                commitment.proving_system_aux_data_commitment,
+                commitment.proof_generator_addr,
+                aligned_verification_data.batch_merkle_root,
+                merkle_proof.into(),
+                U256::from(aligned_verification_data.index_in_batch),
+            )
+            .call()
+            .await
+            .map_err(|e| SubmitError::EthereumProviderError(e.to_string()))?)
+    }
+
+    /// Checks the batch of the proof until it is verified, for at most `timeout`.
+    pub async fn wait_for_verification(
+        &self,
+        rpc_url: &str,
+        aligned_verification_data: &AlignedVerificationData,
+        timeout: Duration,
+    ) -> Result<(), AlignedError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.is_verified(rpc_url, aligned_verification_data).await? {
+                return Ok(());
+            }
+            if Instant::now() + VERIFICATION_INTERVAL > deadline {
+                return Err(SubmitError::GenericError(format!(
+                    "Batch 0x{} was not verified after {}s",
+                    hex::encode(aligned_verification_data.batch_merkle_root),
+                    timeout.as_secs()
+                )))?;
+            }
+            info!(
+                "Batch not verified yet, checking again in {}s",
+                VERIFICATION_INTERVAL.as_secs()
+            );
+            tokio::time::sleep(VERIFICATION_INTERVAL).await;
+        }
+    }
+}
+
+fn provider(rpc_url: &str) -> Result<Provider<Http>, SubmitError> {
+    Provider::<Http>::try_from(rpc_url)
+        .map_err(|e| SubmitError::EthereumProviderError(e.to_string()))
+}
+
+fn payment_service(
+    profile: &NetworkProfile,
+    rpc_url: &str,
+) -> Result<BatcherPaymentService<Provider<Http>>, SubmitError> {
+    Ok(BatcherPaymentService::new(
+        profile.batcher_payment_service,
+        provider(rpc_url)?.into(),
+    ))
+}
@@ -0,0 +1,155 @@
+use clap::ValueEnum;
+use log::error;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::{ExitCode, Stdio},
+};
+
+use crate::batch::BatchOutput;
+use crate::AlignedSubmission;
+
+/// Format of the result printed by zkRust once a command finishes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable logs
+    #[default]
+    Human,
+    /// A single JSON object on stdout, logs on stderr
+    Json,
+}
+
+impl OutputFormat {
+    /// Stdout handle for child processes (cargo and the generated host).
+    /// In JSON mode their output is redirected to stderr so stdout only carries the result object.
+    pub fn child_stdout(&self) -> Stdio {
+        match self {
+            OutputFormat::Human => Stdio::inherit(),
+            OutputFormat::Json => Stdio::from(io::stderr()),
+        }
+    }
+}
+
+/// Paths of the files produced by a proving run.
+#[derive(Debug, Serialize)]
+pub struct ProofArtifacts {
+    pub proof: PathBuf,
+    /// SP1 ELF or RISC0 image ID
+    pub program: PathBuf,
+    pub public_input: PathBuf,
+    /// Which of the public values are public inputs, only written when the program declares them
+    pub public_inputs: Option<PathBuf>,
+}
+
+/// Result of a `prove-*` command.
+#[derive(Debug, Serialize)]
+pub struct ProofOutput {
+    pub backend: &'static str,
+    pub artifacts: ProofArtifacts,
+    pub program_id: Option<String>,
+    pub public_values: Option<String>,
+    pub cycles: Option<u64>,
+    pub aligned: Option<AlignedSubmission>,
+}
+
+impl ProofArtifacts {
+    pub fn new(
+        proof: PathBuf,
+        program: PathBuf,
+        public_input: PathBuf,
+        public_inputs: PathBuf,
+    ) -> Self {
+        ProofArtifacts {
+            proof,
+            program,
+            public_input,
+            public_inputs: public_inputs.exists().then_some(public_inputs),
+        }
+    }
+}
+
+impl ProofOutput {
+    /// Collects the proof artifacts written by the host. Program ID and cycle count are optional
+    /// as they are only reported by hosts that support them.
+    pub fn from_artifacts(
+        backend: &'static str,
+        artifacts: ProofArtifacts,
+        program_id_path: &Path,
+        cycles_path: &Path,
+    ) -> io::Result<Self> {
+        let public_values = hex::encode(std::fs::read(&artifacts.public_input)?);
+        Ok(ProofOutput {
+            backend,
+            artifacts,
+            program_id: read_program_id(program_id_path),
+            public_values: Some(public_values),
+            cycles: std::fs::read_to_string(cycles_path)
+                .ok()
+                .and_then(|cycles| cycles.trim().parse().ok()),
+            aligned: None,
+        })
+    }
+}
+
+// Program IDs are either stored as hex text (SP1 vkey hash) or as raw bytes (RISC0 image ID).
+fn read_program_id(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    match String::from_utf8(bytes.clone()) {
+        Ok(id) if id.trim().starts_with("0x") => Some(id.trim().to_string()),
+        _ => Some(format!("0x{}", hex::encode(bytes))),
+    }
+}
+
+/// Prints the result of a command. In JSON mode a single object is written to stdout,
+/// in human mode only errors are logged since progress was already reported.
+pub fn report<T: Serialize>(format: OutputFormat, result: io::Result<T>) -> ExitCode {
+    match (format, result) {
+        (OutputFormat::Human, Ok(_)) => ExitCode::SUCCESS,
+        (OutputFormat::Human, Err(e)) => {
+            error!("{}", e);
+            ExitCode::FAILURE
+        }
+        (OutputFormat::Json, Ok(output)) => match serde_json::to_value(&output) {
+            Ok(mut data) => {
+                if let Some(fields) = data.as_object_mut() {
+                    fields.insert("status".to_string(), json!("success"));
+                }
+                println!("{}", data);
+                ExitCode::SUCCESS
+            }
+            Err(e) => report::<()>(format, Err(io::Error::other(e))),
+        },
+        (OutputFormat::Json, Err(e)) => {
+            println!("{}", json!({ "status": "error", "error": e.to_string() }));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the result of `prove --inputs-dir`. When inputs failed, the proofs of the others are
+/// still printed, with an `error` status, and zkRust exits with an error.
+pub fn report_batch(format: OutputFormat, result: io::Result<BatchOutput>) -> ExitCode {
+    let output = match result {
+        Ok(output) if output.failed > 0 => output,
+        result => return report(format, result),
+    };
+    let error = format!(
+        "{} of {} proof(s) failed",
+        output.failed,
+        output.proofs.len()
+    );
+    match (format, serde_json::to_value(&output)) {
+        (OutputFormat::Human, _) => error!("{}", error),
+        (OutputFormat::Json, Ok(mut data)) => {
+            if let Some(fields) = data.as_object_mut() {
+                fields.insert("status".to_string(), json!("error"));
+                fields.insert("error".to_string(), json!(error));
+            }
+            println!("{}", data);
+        }
+        (OutputFormat::Json, Err(e)) => return report::<()>(format, Err(io::Error::other(e))),
+    }
+    ExitCode::FAILURE
+}
@@ -0,0 +1,447 @@
+use clap::Args;
+use log::{error, info, warn};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{manifest, risc0, sp1, Backend, ProofArgs};
+
+const REGISTRY_SOURCE: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+/// Crate forked by a zkVM to call its precompiles.
+#[derive(Debug)]
+pub struct Patch {
+    /// Name of the crate on crates.io
+    pub name: &'static str,
+    /// Version of the crate the fork is based on
+    pub version: &'static str,
+    /// Operations of the crate computed by the precompiles
+    pub accelerates: &'static str,
+    /// Source of the fork, written as its `[patch.crates-io]` entry
+    pub source: &'static str,
+}
+
+/// Patch applied to the guest manifest for a locked dependency.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedPatch {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub locked_version: String,
+    pub accelerates: &'static str,
+}
+
+#[derive(Args, Debug)]
+pub struct PrecompilesArgs {
+    #[clap(
+        long = "backend",
+        value_enum,
+        help = "zkVM the program is executed with, defaults to `backend` in zkrust.toml"
+    )]
+    pub backend: Option<Backend>,
+    #[command(flatten)]
+    pub proof: ProofArgs,
+}
+
+/// Dependency of the program accelerated by the precompiles of a backend.
+#[derive(Debug, Serialize)]
+pub struct AcceleratedCrate {
+    #[serde(flatten)]
+    pub patch: AppliedPatch,
+    /// `file:line` of the uses of the crate in the project
+    pub calls: Vec<String>,
+}
+
+/// Result of the `precompiles` command.
+#[derive(Debug, Serialize)]
+pub struct PrecompilesOutput {
+    pub backend: Backend,
+    pub crates: Vec<AcceleratedCrate>,
+    pub cycles_without_precompiles: u64,
+    pub cycles_with_precompiles: u64,
+}
+
+impl PrecompilesOutput {
+    /// Logs the accelerated crates and the cycles saved.
+    pub fn log(&self) {
+        log_crates(self.backend, &self.crates);
+        let saved = self
+            .cycles_without_precompiles
+            .saturating_sub(self.cycles_with_precompiles);
+        info!(
+            "{} cycles without precompiles, {} with --precompiles ({} saved, {:.1}%)",
+            self.cycles_without_precompiles,
+            self.cycles_with_precompiles,
+            saved,
+            saved as f64 * 100.0 / self.cycles_without_precompiles.max(1) as f64
+        );
+    }
+}
+
+/// Guest manifest with the precompile patches of the locked dependencies. The manifest is
+/// restored when this is dropped, whether the proof succeeded or not.
+#[derive(Debug)]
+pub struct PatchedManifest {
+    path: PathBuf,
+    original: String,
+    pub patches: Vec<AppliedPatch>,
+}
+
+impl PatchedManifest {
+    /// Patches the dependencies locked in the `Cargo.lock` of the project, or of the generated
+    /// guest when the project has none, whose version is compatible with a patch of `backend`.
+    pub fn apply(backend: Backend, guest_path: &Path, home_dir: &Path) -> io::Result<Self> {
+        let path = home_dir.join(guest_cargo_toml(backend));
+        let original = fs::read_to_string(&path)?;
+        let patches = matching_patches(backend, guest_path, home_dir, true)?;
+
+        let mut manifest = PatchedManifest {
+            path,
+            original,
+            patches,
+        };
+        if manifest.patches.is_empty() {
+            warn!(
+                "No dependency of the program has a {:?} precompile patch",
+                backend
+            );
+            return Ok(manifest);
+        }
+        let mut sources = toml::Table::new();
+        for applied in &manifest.patches {
+            if let Some(patch) = registry(backend)
+                .iter()
+                .find(|patch| patch.name == applied.name)
+            {
+                let entry = format!("{} = {}", patch.name, patch.source)
+                    .parse::<toml::Table>()
+                    .map_err(io::Error::other)?;
+                sources.extend(entry);
+            }
+        }
+        // Patches declared in [package.metadata.zkrust] take precedence over the built-in ones
+        let mut guest_manifest = manifest::read_manifest(&manifest.path)?;
+        let added = manifest::add_patches(&mut guest_manifest, sources, false)?;
+        manifest::write_manifest(&manifest.path, &guest_manifest)?;
+        manifest.patches.retain(|applied| {
+            let kept = added.iter().any(|name| name == applied.name);
+            if !kept {
+                info!(
+                    "`{}` is patched by the project, the {:?} precompile patch is not applied",
+                    applied.name, backend
+                );
+            }
+            kept
+        });
+        for applied in &manifest.patches {
+            info!(
+                "Accelerating `{}` {} with {:?} precompiles",
+                applied.name, applied.locked_version, backend
+            );
+        }
+        Ok(manifest)
+    }
+}
+
+impl Drop for PatchedManifest {
+    fn drop(&mut self) {
+        if let Err(e) = fs::write(&self.path, &self.original) {
+            error!(
+                "Failed to remove the precompile patches from {:?}: {}",
+                self.path, e
+            );
+        }
+    }
+}
+
+/// Patches of `backend` matching the dependencies locked for the program, see [`PatchedManifest::apply`].
+/// Incompatible versions are only reported when `warn_unpatched` is set.
+pub fn matching_patches(
+    backend: Backend,
+    guest_path: &Path,
+    home_dir: &Path,
+    warn_unpatched: bool,
+) -> io::Result<Vec<AppliedPatch>> {
+    let guest_dir = match backend {
+        Backend::Sp1 => sp1::SP1_SRC_DIR,
+        Backend::Risc0 => risc0::RISC0_SRC_DIR,
+    };
+    let locked = locked_packages(guest_path, &home_dir.join(guest_dir))?;
+
+    let mut patches = vec![];
+    for patch in registry(backend) {
+        let versions: Vec<&String> = locked
+            .iter()
+            .filter(|(name, _)| name == patch.name)
+            .map(|(_, version)| version)
+            .collect();
+        match versions
+            .iter()
+            .find(|version| is_compatible(version, patch.version))
+        {
+            Some(version) => {
+                if warn_unpatched && *version != patch.version {
+                    warn!(
+                        "`{}` {} is locked but the {:?} patch is {}, cargo only uses it if the version requirement allows {}",
+                        patch.name, version, backend, patch.version, patch.version
+                    );
+                }
+                patches.push(AppliedPatch {
+                    name: patch.name,
+                    version: patch.version,
+                    locked_version: version.to_string(),
+                    accelerates: patch.accelerates,
+                });
+            }
+            None if warn_unpatched => {
+                for version in versions {
+                    warn!(
+                        "`{}` {} is not accelerated, {:?} only patches version {}",
+                        patch.name, version, backend, patch.version
+                    );
+                }
+            }
+            None => {}
+        }
+    }
+    Ok(patches)
+}
+
+fn registry(backend: Backend) -> &'static [Patch] {
+    match backend {
+        Backend::Sp1 => sp1::SP1_PATCHES,
+        Backend::Risc0 => risc0::RISC0_PATCHES,
+    }
+}
+
+fn guest_cargo_toml(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Sp1 => sp1::SP1_GUEST_CARGO_TOML,
+        Backend::Risc0 => risc0::RISC0_GUEST_CARGO_TOML,
+    }
+}
+
+// Name and version of the crates.io packages in the lockfile. The project lockfile reflects the
+// versions the user tested with. When the project has none the guest lockfile is resolved again,
+// the one in the workspace may be left from a previous program.
+fn locked_packages(
+    guest_path: &Path,
+    workspace_guest_dir: &Path,
+) -> io::Result<Vec<(String, String)>> {
+    let mut lockfile = guest_path.join("Cargo.lock");
+    if !lockfile.exists() {
+        lockfile = workspace_guest_dir.join("Cargo.lock");
+        let status = Command::new("cargo")
+            .arg("generate-lockfile")
+            .current_dir(workspace_guest_dir)
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(
+                "Failed to resolve the dependencies of the program",
+            ));
+        }
+    }
+    let lock = fs::read_to_string(&lockfile)?
+        .parse::<toml::Table>()
+        .map_err(io::Error::other)?;
+    let packages = lock
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .map(|packages| packages.as_slice())
+        .unwrap_or_default();
+    Ok(packages
+        .iter()
+        .filter(|package| {
+            package.get("source").and_then(|source| source.as_str()) == Some(REGISTRY_SOURCE)
+        })
+        .filter_map(|package| {
+            Some((
+                package.get("name")?.as_str()?.to_string(),
+                package.get("version")?.as_str()?.to_string(),
+            ))
+        })
+        .collect())
+}
+
+// Versions are compatible when cargo would accept either for a caret requirement on the other:
+// same major version, or same minor version below 1.0.
+fn is_compatible(locked: &str, patched: &str) -> bool {
+    let series = |version: &str| -> Vec<String> {
+        let parts: Vec<String> = version
+            .split(['.', '-', '+'])
+            .take(2)
+            .map(str::to_string)
+            .collect();
+        match parts.first().map(String::as_str) {
+            Some("0") => parts,
+            _ => parts.into_iter().take(1).collect(),
+        }
+    };
+    series(locked) == series(patched)
+}
+
+/// Dependencies of the program that `backend` accelerates, with the lines of the project using them.
+pub fn accelerated_crates(
+    backend: Backend,
+    guest_path: &Path,
+    home_dir: &Path,
+) -> io::Result<Vec<AcceleratedCrate>> {
+    matching_patches(backend, guest_path, home_dir, false)?
+        .into_iter()
+        .map(|patch| {
+            Ok(AcceleratedCrate {
+                calls: crate_uses(guest_path, patch.name)?,
+                patch,
+            })
+        })
+        .collect()
+}
+
+/// Suggests `--precompiles` when the program depends on crates the backend accelerates.
+/// Failing to resolve the dependencies does not stop the proof, the suggestion is skipped.
+pub fn suggest(backend: Backend, guest_path: &Path, home_dir: &Path) {
+    let crates = match accelerated_crates(backend, guest_path, home_dir) {
+        Ok(crates) => crates,
+        Err(e) => {
+            warn!("Failed to look for accelerated dependencies: {}", e);
+            return;
+        }
+    };
+    if crates.is_empty() {
+        return;
+    }
+    log_crates(backend, &crates);
+    info!(
+        "Prove with --precompiles to accelerate them, `zkRust precompiles --backend {} {}` estimates the cycles saved",
+        backend.name(),
+        guest_path.display()
+    );
+}
+
+fn log_crates(backend: Backend, crates: &[AcceleratedCrate]) {
+    for accelerated in crates {
+        info!(
+            "`{}` {} is accelerated by {:?} precompiles ({})",
+            accelerated.patch.name,
+            accelerated.patch.locked_version,
+            backend,
+            accelerated.patch.accelerates
+        );
+        for call in &accelerated.calls {
+            info!("    used at {}", call);
+        }
+    }
+}
+
+// Paths of `crate_name` in the sources of the project, e.g. `sha2::Sha256` or `use sha2::{...}`.
+fn crate_uses(guest_path: &Path, crate_name: &str) -> io::Result<Vec<String>> {
+    let path_regex =
+        Regex::new(&format!(r"\b{}::", crate_name.replace('-', "_"))).map_err(io::Error::other)?;
+    let mut uses = vec![];
+    for dir in ["src", "lib"] {
+        let dir = guest_path.join(dir);
+        if dir.exists() {
+            crate_uses_in(guest_path, &dir, &path_regex, &mut uses)?;
+        }
+    }
+    Ok(uses)
+}
+
+fn crate_uses_in(
+    guest_path: &Path,
+    dir: &Path,
+    path_regex: &Regex,
+    uses: &mut Vec<String>,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            crate_uses_in(guest_path, &path, path_regex, uses)?;
+        } else if path.extension().is_some_and(|extension| extension == "rs") {
+            let relative = path.strip_prefix(guest_path).unwrap_or(&path);
+            for (index, line) in fs::read_to_string(&path)?.lines().enumerate() {
+                if path_regex.is_match(line) && !line.trim_start().starts_with("//") {
+                    uses.push(format!("{}:{}", relative.display(), index + 1));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_compatible_follows_caret_requirements() {
+        assert!(is_compatible("0.10.8", "0.10.6"));
+        assert!(is_compatible("0.10.6", "0.10.6"));
+        assert!(!is_compatible("0.9.9", "0.10.6"));
+        assert!(is_compatible("2.1.3", "2.0.2"));
+        assert!(!is_compatible("1.0.0", "2.0.2"));
+        assert!(is_compatible("0.5.5-pre.1", "0.5.5"));
+    }
+
+    #[test]
+    fn matching_patches_of_the_project_lockfile() {
+        let guest_path = tempfile::tempdir().unwrap();
+        fs::write(
+            guest_path.path().join("Cargo.lock"),
+            format!(
+                r#"version = 3
+
+[[package]]
+name = "sha2"
+version = "0.10.8"
+source = "{REGISTRY_SOURCE}"
+
+[[package]]
+name = "sha3"
+version = "0.9.1"
+source = "{REGISTRY_SOURCE}"
+
+[[package]]
+name = "tiny-keccak"
+version = "2.0.2"
+source = "git+https://github.com/debris/tiny-keccak"
+
+[[package]]
+name = "ed25519-consensus"
+version = "2.1.0"
+source = "{REGISTRY_SOURCE}"
+
+[[package]]
+name = "program"
+version = "0.1.0"
+"#
+            ),
+        )
+        .unwrap();
+
+        let patches = matching_patches(
+            Backend::Sp1,
+            guest_path.path(),
+            Path::new("/nonexistent"),
+            false,
+        )
+        .unwrap();
+        let patches: Vec<(&str, &str, &str)> = patches
+            .iter()
+            .map(|patch| (patch.name, patch.version, patch.locked_version.as_str()))
+            .collect();
+        // sha3 0.9 is not compatible with the patch and tiny-keccak is not from crates.io
+        assert_eq!(
+            patches,
+            vec![
+                ("sha2", "0.10.6", "0.10.8"),
+                ("ed25519-consensus", "2.1.0", "2.1.0"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use clap::Args;
+use log::info;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{utils, Backend, ProofArgs};
+
+/// Environment variable of the SP1 executor writing the program counter of every cycle.
+pub const SP1_TRACE_ENV: &str = "TRACE_FILE";
+/// Environment variable of the RISC0 executor writing a pprof profile.
+pub const RISC0_PPROF_ENV: &str = "RISC0_PPROF_OUT";
+
+// Relative to the `--proof-data-path` directory
+pub const SP1_TRACE_PATH: &str = "sp1/sp1.trace";
+pub const SP1_FOLDED_PATH: &str = "sp1/sp1.folded";
+pub const RISC0_PPROF_PATH: &str = "risc0/risc0.pprof";
+
+// Functions logged once the profile is written
+const TOP_FUNCTIONS: usize = 10;
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+#[derive(Args, Debug)]
+pub struct ProfileArgs {
+    #[clap(
+        long = "backend",
+        value_enum,
+        help = "zkVM the program is executed with, defaults to `backend` in zkrust.toml"
+    )]
+    pub backend: Option<Backend>,
+    #[command(flatten)]
+    pub proof: ProofArgs,
+}
+
+/// Cycles spent in a function, excluding the functions it calls.
+#[derive(Debug, Serialize)]
+pub struct FunctionCycles {
+    pub function: String,
+    pub cycles: u64,
+}
+
+/// Result of the `profile` command.
+#[derive(Debug, Serialize)]
+pub struct ProfileOutput {
+    pub backend: Backend,
+    /// pprof profile (RISC0) or folded stacks (SP1)
+    pub profile: PathBuf,
+    pub format: &'static str,
+    /// Functions with the most cycles, only known for folded stacks
+    pub top_functions: Vec<FunctionCycles>,
+}
+
+/// Host environment writing the raw profile of `backend` during execution.
+pub fn profile_envs(backend: Backend, proof_data_dir: &Path) -> Vec<(&'static str, String)> {
+    let raw_profile = match backend {
+        Backend::Sp1 => (SP1_TRACE_ENV, proof_data_dir.join(SP1_TRACE_PATH)),
+        Backend::Risc0 => (RISC0_PPROF_ENV, proof_data_dir.join(RISC0_PPROF_PATH)),
+    };
+    vec![
+        (utils::EXECUTE_ENV, "1".to_string()),
+        (raw_profile.0, raw_profile.1.display().to_string()),
+    ]
+}
+
+/// Turns the program counter trace of the SP1 executor into folded stacks, one line per call
+/// stack with the cycles spent in it, which flamegraph tools read directly.
+pub fn fold_sp1_trace(elf: &Path, trace: &Path, folded: &Path) -> io::Result<Vec<FunctionCycles>> {
+    let functions = elf_functions(&fs::read(elf)?)?;
+    let trace = fs::read(trace)?;
+
+    let mut stacks: BTreeMap<Vec<usize>, u64> = BTreeMap::new();
+    let mut stack: Vec<usize> = vec![];
+    let mut cycles = 0;
+    // The executor writes each program counter as a big endian u32
+    for pc in trace
+        .chunks_exact(4)
+        .map(|pc| u32::from_be_bytes([pc[0], pc[1], pc[2], pc[3]]))
+    {
+        let Some(function) = find_function(&functions, pc) else {
+            continue;
+        };
+        if stack.last() != Some(&function) {
+            if cycles > 0 {
+                *stacks.entry(stack.clone()).or_default() += cycles;
+                cycles = 0;
+            }
+            if pc == functions[function].start {
+                // Entering a function at its first instruction is a call
+                stack.push(function);
+            } else if let Some(caller) = stack.iter().rposition(|&caller| caller == function) {
+                // Landing back in a function of the stack is a return
+                stack.truncate(caller + 1);
+            } else {
+                // Anything else is a tail call or a jump into another function
+                stack.pop();
+                stack.push(function);
+            }
+        }
+        cycles += 1;
+    }
+    if cycles > 0 {
+        *stacks.entry(stack).or_default() += cycles;
+    }
+
+    let mut file = io::BufWriter::new(fs::File::create(folded)?);
+    let mut exclusive: HashMap<usize, u64> = HashMap::new();
+    for (stack, cycles) in &stacks {
+        let names: Vec<&str> = stack
+            .iter()
+            .map(|&function| functions[function].name.as_str())
+            .collect();
+        writeln!(file, "{} {}", names.join(";"), cycles)?;
+        if let Some(&function) = stack.last() {
+            *exclusive.entry(function).or_default() += cycles;
+        }
+    }
+    file.flush()?;
+
+    let mut top_functions: Vec<FunctionCycles> = exclusive
+        .into_iter()
+        .map(|(function, cycles)| FunctionCycles {
+            function: functions[function].name.clone(),
+            cycles,
+        })
+        .collect();
+    top_functions.sort_by_key(|function| std::cmp::Reverse(function.cycles));
+    top_functions.truncate(TOP_FUNCTIONS);
+    Ok(top_functions)
+}
+
+/// Logs the functions with the most cycles.
+pub fn log_top_functions(top_functions: &[FunctionCycles]) {
+    for function in top_functions {
+        info!("{:>12}  {}", function.cycles, function.function);
+    }
+}
+
+struct Function {
+    start: u32,
+    end: u32,
+    name: String,
+}
+
+// Function symbols of a 32 bit little endian ELF, sorted by address.
+fn elf_functions(elf: &[u8]) -> io::Result<Vec<Function>> {
+    if elf.get(..6) != Some(&[0x7f, b'E', b'L', b'F', 1, 1]) {
+        return Err(io::Error::other(
+            "Guest program is not a 32 bit little endian ELF",
+        ));
+    }
+    let section_offset = read_u32(elf, 0x20)? as usize;
+    let section_size = read_u16(elf, 0x2e)? as usize;
+    let section_count = read_u16(elf, 0x30)? as usize;
+    let section = |index: usize| section_offset + index * section_size;
+
+    let mut functions = vec![];
+    for index in 0..section_count {
+        if read_u32(elf, section(index) + 4)? != SHT_SYMTAB {
+            continue;
+        }
+        let symbols_offset = read_u32(elf, section(index) + 16)? as usize;
+        let symbols_size = read_u32(elf, section(index) + 20)? as usize;
+        let names_section = section(read_u32(elf, section(index) + 24)? as usize);
+        let names_offset = read_u32(elf, names_section + 16)? as usize;
+
+        for symbol in (symbols_offset..symbols_offset + symbols_size).step_by(16) {
+            let info = *elf.get(symbol + 12).ok_or_else(truncated)?;
+            let size = read_u32(elf, symbol + 8)?;
+            if info & 0xf != STT_FUNC || size == 0 {
+                continue;
+            }
+            let start = read_u32(elf, symbol + 4)?;
+            let name_start = names_offset + read_u32(elf, symbol)? as usize;
+            let name = elf
+                .get(name_start..)
+                .and_then(|names| names.split(|&byte| byte == 0).next())
+                .ok_or_else(truncated)?;
+            functions.push(Function {
+                start,
+                end: start + size,
+                // Folded stacks separate frames with `;`, which appears in array types
+                name: format!(
+                    "{:#}",
+                    rustc_demangle::demangle(&String::from_utf8_lossy(name))
+                )
+                .replace(';', ":"),
+            });
+        }
+    }
+    if functions.is_empty() {
+        return Err(io::Error::other(
+            "Guest program has no symbols, it may have been stripped",
+        ));
+    }
+    functions.sort_by_key(|function| function.start);
+    Ok(functions)
+}
+
+fn find_function(functions: &[Function], pc: u32) -> Option<usize> {
+    let index = functions.partition_point(|function| function.start <= pc);
+    let function = index.checked_sub(1)?;
+    (pc < functions[function].end).then_some(function)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    let bytes = bytes.get(offset..offset + 4).ok_or_else(truncated)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> io::Result<u16> {
+    let bytes = bytes.get(offset..offset + 2).ok_or_else(truncated)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn truncated() -> io::Error {
+    io::Error::other("Guest ELF is truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOL_SIZE: usize = 16;
+    const SECTION_HEADER_SIZE: usize = 40;
+
+    // ELF with a symbol table of `(name, address, size, info)` symbols and its string table.
+    fn elf(symbols: &[(&str, u32, u32, u8)]) -> Vec<u8> {
+        let mut names = vec![0];
+        let mut symbol_table = vec![0; SYMBOL_SIZE];
+        for (name, address, size, info) in symbols {
+            symbol_table.extend((names.len() as u32).to_le_bytes());
+            symbol_table.extend(address.to_le_bytes());
+            symbol_table.extend(size.to_le_bytes());
+            symbol_table.extend([*info, 0, 0, 0]);
+            names.extend(name.as_bytes());
+            names.push(0);
+        }
+
+        let mut elf = vec![0; 52];
+        elf[..6].copy_from_slice(&[0x7f, b'E', b'L', b'F', 1, 1]);
+        let names_offset = elf.len();
+        elf.extend(&names);
+        let symbols_offset = elf.len();
+        elf.extend(&symbol_table);
+        let sections_offset = elf.len();
+        elf[0x20..0x24].copy_from_slice(&(sections_offset as u32).to_le_bytes());
+        elf[0x2e..0x30].copy_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+        elf[0x30..0x32].copy_from_slice(&3u16.to_le_bytes());
+
+        // Null section, symbol table linked to the string table, string table
+        let section = |kind: u32, offset: usize, size: usize, link: u32| {
+            let mut header = vec![0; SECTION_HEADER_SIZE];
+            header[4..8].copy_from_slice(&kind.to_le_bytes());
+            header[16..20].copy_from_slice(&(offset as u32).to_le_bytes());
+            header[20..24].copy_from_slice(&(size as u32).to_le_bytes());
+            header[24..28].copy_from_slice(&link.to_le_bytes());
+            header
+        };
+        elf.extend(section(0, 0, 0, 0));
+        elf.extend(section(SHT_SYMTAB, symbols_offset, symbol_table.len(), 2));
+        elf.extend(section(3, names_offset, names.len(), 0));
+        elf
+    }
+
+    fn program() -> Vec<u8> {
+        elf(&[
+            ("helper", 0x200, 0x10, STT_FUNC),
+            ("main", 0x100, 0x20, STT_FUNC),
+            ("<[u8; 4]>::leaf", 0x300, 0x8, STT_FUNC),
+            ("DATA", 0x400, 0x40, 1),
+            ("empty", 0x500, 0, STT_FUNC),
+        ])
+    }
+
+    #[test]
+    fn elf_functions_are_sorted_function_symbols() {
+        let functions = elf_functions(&program()).unwrap();
+        let functions: Vec<(&str, u32, u32)> = functions
+            .iter()
+            .map(|function| (function.name.as_str(), function.start, function.end))
+            .collect();
+        assert_eq!(
+            functions,
+            vec![
+                ("main", 0x100, 0x120),
+                ("helper", 0x200, 0x210),
+                ("<[u8: 4]>::leaf", 0x300, 0x308),
+            ]
+        );
+    }
+
+    #[test]
+    fn elf_functions_of_invalid_programs() {
+        assert!(elf_functions(b"not an elf").is_err());
+        // Stripped
+        assert!(elf_functions(&elf(&[("DATA", 0x400, 0x40, 1)])).is_err());
+        assert!(elf_functions(&program()[..100]).is_err());
+    }
+
+    #[test]
+    fn fold_sp1_trace_follows_calls_and_returns() {
+        let dir = tempfile::tempdir().unwrap();
+        let (elf_path, trace, folded) = (
+            dir.path().join("sp1.elf"),
+            dir.path().join("sp1.trace"),
+            dir.path().join("sp1.folded"),
+        );
+        fs::write(&elf_path, program()).unwrap();
+        let pcs: [u32; 13] = [
+            0x100, 0x104, // main
+            0x200, 0x204, // calls helper
+            0x300, 0x304, // which calls leaf
+            0x208, // returns to helper
+            0x108, 0x10c, // returns to main
+            0x200, // calls helper again
+            0x600, // outside of any function
+            0x110, 0x114, // returns to main
+        ];
+        fs::write(
+            &trace,
+            pcs.iter()
+                .flat_map(|pc| pc.to_be_bytes())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let top_functions = fold_sp1_trace(&elf_path, &trace, &folded).unwrap();
+        assert_eq!(
+            fs::read_to_string(&folded).unwrap(),
+            "main 6\nmain;helper 4\nmain;helper;<[u8: 4]>::leaf 2\n"
+        );
+        let top_functions: Vec<(&str, u64)> = top_functions
+            .iter()
+            .map(|function| (function.function.as_str(), function.cycles))
+            .collect();
+        assert_eq!(
+            top_functions,
+            vec![("main", 6), ("helper", 4), ("<[u8: 4]>::leaf", 2)]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use aligned_sdk::core::types::{
+    AlignedVerificationData, VerificationData, VerificationDataCommitment,
+};
+use ethers::types::{Address, U256};
+use lambdaworks_crypto::merkle_tree::proof::Proof;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Records are written next to the proof they track, e.g. `proof_data/sp1/sp1.submission.json`.
+pub const RECORD_EXTENSION: &str = "submission.json";
+
+/// Proof sent to the batcher. Written before sending it so a dropped connection or a batch
+/// that takes too long to verify can be resumed with `zkRust aligned status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionRecord {
+    /// `--network` the proof was submitted to
+    pub network: String,
+    pub proof: PathBuf,
+    pub nonce: U256,
+    pub max_fee: U256,
+    /// Unix time the proof was first sent
+    pub submitted_at: u64,
+    pub proof_generator_addr: Address,
+    pub proof_commitment: String,
+    pub pub_input_commitment: String,
+    pub program_id_commitment: String,
+    /// Only known once the batcher answered with the batch of the proof
+    pub inclusion: Option<BatchInclusion>,
+    pub verified: bool,
+}
+
+/// Position of a proof in its batch, as answered by the batcher.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchInclusion {
+    pub batch_merkle_root: String,
+    pub index_in_batch: usize,
+    pub merkle_path: Vec<String>,
+    pub verification_data_path: PathBuf,
+}
+
+impl SubmissionRecord {
+    pub fn new(
+        network: &str,
+        proof: &Path,
+        verification_data: &VerificationData,
+        nonce: U256,
+        max_fee: U256,
+    ) -> io::Result<Self> {
+        let commitment = VerificationDataCommitment::from(verification_data);
+        Ok(SubmissionRecord {
+            network: network.to_string(),
+            proof: proof.to_path_buf(),
+            nonce,
+            max_fee,
+            submitted_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(io::Error::other)?
+                .as_secs(),
+            proof_generator_addr: verification_data.proof_generator_addr,
+            proof_commitment: hex::encode(commitment.proof_commitment),
+            pub_input_commitment: hex::encode(commitment.pub_input_commitment),
+            program_id_commitment: hex::encode(commitment.proving_system_aux_data_commitment),
+            inclusion: None,
+            verified: false,
+        })
+    }
+
+    /// Path of the record of the proof at `proof`.
+    pub fn path(proof: &Path) -> PathBuf {
+        proof.with_extension(RECORD_EXTENSION)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| io::Error::other(format!("Invalid record {}: {}", path.display(), e)))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    /// Stores the batch of the proof answered by the batcher.
+    pub fn set_inclusion(
+        &mut self,
+        aligned_verification_data: &AlignedVerificationData,
+        verification_data_path: PathBuf,
+    ) {
+        self.inclusion = Some(BatchInclusion {
+            batch_merkle_root: hex::encode(aligned_verification_data.batch_merkle_root),
+            index_in_batch: aligned_verification_data.index_in_batch,
+            merkle_path: aligned_verification_data
+                .batch_inclusion_proof
+                .merkle_path
+                .iter()
+                .map(hex::encode)
+                .collect(),
+            verification_data_path,
+        });
+    }
+
+    /// Rebuilds the answer of the batcher, if the proof got one, to check its batch on chain.
+    pub fn aligned_verification_data(&self) -> io::Result<Option<AlignedVerificationData>> {
+        let Some(inclusion) = &self.inclusion else {
+            return Ok(None);
+        };
+        Ok(Some(AlignedVerificationData {
+            verification_data_commitment: VerificationDataCommitment {
+                proof_commitment: decode(&self.proof_commitment)?,
+                pub_input_commitment: decode(&self.pub_input_commitment)?,
+                proving_system_aux_data_commitment: decode(&self.program_id_commitment)?,
+                proof_generator_addr: self.proof_generator_addr.into(),
+            },
+            batch_merkle_root: decode(&inclusion.batch_merkle_root)?,
+            batch_inclusion_proof: Proof {
+                merkle_path: inclusion
+                    .merkle_path
+                    .iter()
+                    .map(|node| decode(node))
+                    .collect::<io::Result<_>>()?,
+            },
+            index_in_batch: inclusion.index_in_batch,
+        }))
+    }
+
+    /// Whether `commitment`, a hex prefix with or without `0x`, designates this proof.
+    pub fn matches(&self, commitment: &str) -> bool {
+        let commitment = commitment.trim_start_matches("0x").to_lowercase();
+        !commitment.is_empty() && self.proof_commitment.starts_with(&commitment)
+    }
+}
+
+/// Finds the records under `dir`, recursively.
+pub fn find_records(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut records = vec![];
+    if !dir.is_dir() {
+        return Ok(records);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            records.extend(find_records(&path)?);
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(RECORD_EXTENSION))
+        {
+            records.push(path);
+        }
+    }
+    Ok(records)
+}
+
+fn decode<const N: usize>(hex_value: &str) -> io::Result<[u8; N]> {
+    hex::decode(hex_value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| io::Error::other(format!("Invalid commitment in record: {}", hex_value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aligned_sdk::core::types::ProvingSystemId;
+
+    fn verification_data() -> VerificationData {
+        VerificationData {
+            proving_system: ProvingSystemId::SP1,
+            proof: b"proof".to_vec(),
+            pub_input: Some(b"public values".to_vec()),
+            verification_key: None,
+            vm_program_code: Some(b"elf".to_vec()),
+            proof_generator_addr: Address::repeat_byte(7),
+        }
+    }
+
+    #[test]
+    fn round_trips_the_batch_of_a_proof() {
+        let dir = tempfile::tempdir().unwrap();
+        let proof = dir.path().join("input").join("sp1").join("sp1.proof");
+        fs::create_dir_all(proof.parent().unwrap()).unwrap();
+        let verification_data = verification_data();
+        let mut record = SubmissionRecord::new(
+            "devnet",
+            &proof,
+            &verification_data,
+            U256::from(5),
+            U256::from(100),
+        )
+        .unwrap();
+        let path = SubmissionRecord::path(&proof);
+        assert_eq!(path.file_name().unwrap(), "sp1.submission.json");
+        record.write(&path).unwrap();
+        let sent = SubmissionRecord::read(&path).unwrap();
+        assert!(sent.aligned_verification_data().unwrap().is_none());
+
+        let commitment = VerificationDataCommitment::from(&verification_data);
+        let answer = AlignedVerificationData {
+            verification_data_commitment: commitment.clone(),
+            batch_merkle_root: [9; 32],
+            batch_inclusion_proof: Proof {
+                merkle_path: vec![[4; 32], [5; 32]],
+            },
+            index_in_batch: 1,
+        };
+        record.set_inclusion(&answer, PathBuf::from("batch.json"));
+        record.write(&path).unwrap();
+        assert_eq!(find_records(dir.path()).unwrap(), vec![path.clone()]);
+
+        let read = SubmissionRecord::read(&path).unwrap();
+        assert_eq!(read.network, "devnet");
+        assert_eq!(read.proof, proof);
+        assert_eq!(read.nonce, U256::from(5));
+        assert_eq!(read.max_fee, U256::from(100));
+        assert_eq!(read.proof_generator_addr, Address::repeat_byte(7));
+        assert!(!read.verified);
+        let rebuilt = read.aligned_verification_data().unwrap().unwrap();
+        let rebuilt_commitment = &rebuilt.verification_data_commitment;
+        assert_eq!(
+            rebuilt_commitment.proof_commitment,
+            commitment.proof_commitment
+        );
+        assert_eq!(
+            rebuilt_commitment.pub_input_commitment,
+            commitment.pub_input_commitment
+        );
+        assert_eq!(
+            rebuilt_commitment.proving_system_aux_data_commitment,
+            commitment.proving_system_aux_data_commitment
+        );
+        assert_eq!(
+            rebuilt_commitment.proof_generator_addr,
+            commitment.proof_generator_addr
+        );
+        assert_eq!(rebuilt.batch_merkle_root, [9; 32]);
+        assert_eq!(
+            rebuilt.batch_inclusion_proof.merkle_path,
+            vec![[4; 32], [5; 32]]
+        );
+        assert_eq!(rebuilt.index_in_batch, 1);
+
+        let mut corrupted = read;
+        corrupted.proof_commitment.truncate(10);
+        assert!(corrupted.aligned_verification_data().is_err());
+    }
+
+    #[test]
+    fn matches_prefixes_of_the_proof_commitment() {
+        let record = SubmissionRecord::new(
+            "devnet",
+            Path::new("sp1.proof"),
+            &verification_data(),
+            U256::zero(),
+            U256::zero(),
+        )
+        .unwrap();
+        let commitment = record.proof_commitment.clone();
+        assert!(record.matches(&commitment));
+        assert!(record.matches(&commitment[..8]));
+        assert!(record.matches(&format!("0x{}", &commitment[..8])));
+        assert!(record.matches(&format!("0x{}", commitment[..8].to_uppercase())));
+
+        let other = if commitment.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        assert!(!record.matches(&format!("{}{}", other, &commitment[1..8])));
+        assert!(!record.matches(&format!("{}0", commitment)));
+        assert!(!record.matches(""));
+        assert!(!record.matches("0x"));
+    }
+}
@@ -1,46 +1,226 @@
 use std::{
-    fs,
+    fs::{self, OpenOptions},
     io::{self, Write},
-    path::PathBuf,
-    process::{Command, ExitStatus},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
 };
 
+use crate::input::{self, HostInput};
+use crate::lint;
+use crate::manifest;
+use crate::precompiles::Patch;
+use crate::source_map::{SourceMap, SourceMaps};
 use crate::utils;
+use crate::Backend;
 
 /// RISC0 workspace directories
 pub const RISC0_WORKSPACE_DIR: &str = "workspaces/risc0/";
 pub const RISC0_SRC_DIR: &str = "workspaces/risc0/methods/guest";
 pub const RISC0_GUEST_MAIN: &str = "workspaces/risc0/methods/guest/src/main.rs";
+pub const RISC0_HOST_DIR: &str = "workspaces/risc0/host";
 pub const RISC0_HOST_MAIN: &str = "workspaces/risc0/host/src/main.rs";
+pub const RISC0_HOST_CARGO_TOML: &str = "workspaces/risc0/host/Cargo.toml";
 pub const RISC0_BASE_HOST_CARGO_TOML: &str = "workspaces/base_files/risc0/cargo_host";
 pub const RISC0_BASE_GUEST_CARGO_TOML: &str = "workspaces/base_files/risc0/cargo_guest";
 pub const RISC0_BASE_HOST: &str = "workspaces/base_files/risc0/host";
 pub const RISC0_BASE_HOST_FILE: &str = "workspaces/base_files/risc0/host";
+pub const RISC0_BASE_PUBLIC_INPUTS_GUEST: &str = "workspaces/base_files/risc0/public_inputs_guest";
+pub const RISC0_BASE_PUBLIC_INPUTS_HOST: &str = "workspaces/base_files/risc0/public_inputs_host";
+pub const RISC0_BASE_CYCLE_TRACKER_GUEST: &str = "workspaces/base_files/risc0/cycle_tracker_guest";
 pub const RISC0_GUEST_CARGO_TOML: &str = "workspaces/risc0/methods/guest/Cargo.toml";
 
-// Proof data generation paths
-pub const PROOF_FILE_PATH: &str = "./proof_data/risc0/risc0.proof";
-pub const IMAGE_ID_FILE_PATH: &str = "./proof_data/risc0/risc0.imageid";
-pub const PUBLIC_INPUT_FILE_PATH: &str = "./proof_data/risc0/risc0.pub";
+/// Toolchain and target the RISC0 guest is compiled with
+pub const RISC0_GUEST_TOOLCHAIN: &str = "risc0";
+pub const RISC0_GUEST_TARGET: &str = "riscv32im-risc0-zkvm-elf";
+
+// Proof data generation paths, relative to the `--proof-data-path` directory
+pub const PROOF_FILE_PATH: &str = "risc0/risc0.proof";
+pub const IMAGE_ID_FILE_PATH: &str = "risc0/risc0.imageid";
+pub const PUBLIC_INPUT_FILE_PATH: &str = "risc0/risc0.pub";
+pub const CYCLES_FILE_PATH: &str = "risc0/risc0.cycles";
+pub const EXECUTE_CYCLES_FILE_PATH: &str = "risc0/risc0.execute_cycles";
+pub const PUBLIC_INPUTS_FILE_PATH: &str = "risc0/risc0.public_inputs.json";
 
 //TODO: should we use std or no_std header
 /// RISC0 header added to programs for generating proofs of their execution
 pub const RISC0_GUEST_PROGRAM_HEADER: &str = "#![no_main]\n\nrisc0_zkvm::guest::entry!(main);\n";
+/// RISC0 header of programs with public inputs, `zkrust_main` commits them after running `main()`
+pub const RISC0_GUEST_PUBLIC_INPUTS_HEADER: &str =
+    "#![no_main]\n\nrisc0_zkvm::guest::entry!(zkrust_main);\n";
 
-/// RISC0 Cargo patch for accelerated SHA-256, K256, and bigint-multiplication circuits
-pub const RISC0_ACCELERATION_IMPORT: &str = "\n[patch.crates-io]\nsha2 = { git = \"https://github.com/risc0/RustCrypto-hashes\", tag = \"sha2-v0.10.6-risczero.0\" }\nk256 = { git = \"https://github.com/risc0/RustCrypto-elliptic-curves\", tag = \"k256/v0.13.1-risczero.1\"  }\ncrypto-bigint = { git = \"https://github.com/risc0/RustCrypto-crypto-bigint\", tag = \"v0.5.5-risczero.0\" }";
+/// Crates patched with the precompiles of RISC0 v1.0.1
+pub const RISC0_PATCHES: &[Patch] = &[
+    Patch {
+        name: "sha2",
+        version: "0.10.6",
+        accelerates: "SHA-256 hashing",
+        source: "{ git = \"https://github.com/risc0/RustCrypto-hashes\", tag = \"sha2-v0.10.6-risczero.0\" }",
+    },
+    Patch {
+        name: "k256",
+        version: "0.13.1",
+        accelerates: "secp256k1 arithmetic and ECDSA verification",
+        source: "{ git = \"https://github.com/risc0/RustCrypto-elliptic-curves\", tag = \"k256/v0.13.1-risczero.1\" }",
+    },
+    Patch {
+        name: "crypto-bigint",
+        version: "0.5.5",
+        accelerates: "big integer multiplication",
+        source: "{ git = \"https://github.com/risc0/RustCrypto-crypto-bigint\", tag = \"v0.5.5-risczero.0\" }",
+    },
+];
 
 /// RISC0 User I/O Markers
 // HOST
-pub const RISC0_ENV_BUILDER: &str = "let env = ExecutorEnv::builder()";
-pub const RISC0_IO_HOST: &str = "risc0_zkvm::ExecutorEnv::builder()";
-pub const RISC0_IO_HOST_BUILD: &str = ".build().unwrap();";
+pub const RISC0_HOST_WRITE: &str = "stdin.write";
 
 // GUEST
 pub const RISC0_IO_READ: &str = "risc0_zkvm::guest::env::read();";
+/// Reads a length prefixed slice written by the host, padded to a whole number of words
+pub const RISC0_IO_READ_SLICE: &str = "{ let len: u32 = risc0_zkvm::guest::env::read(); let mut bytes = vec![0u8; (len as usize).div_ceil(4) * 4]; risc0_zkvm::guest::env::read_slice(&mut bytes[..]); bytes.truncate(len as usize); bytes };";
 pub const RISC0_IO_WRITE: &str = "risc0_zkvm::guest::env::write";
 pub const RISC0_IO_COMMIT: &str = "risc0_zkvm::guest::env::commit";
-pub const RISC0_IO_OUT: &str = "receipt.journal.decode().unwrap();";
+pub const RISC0_IO_CYCLE_COUNT: &str = "(risc0_zkvm::guest::env::cycle_count() as u64)";
+pub const RISC0_IO_OUT: &str = "serde::Deserialize::deserialize(&mut journal).unwrap();";
+
+/// Used by the host to write inputs and read the journal, unless the program already depends on serde
+pub const RISC0_HOST_SERDE_DEPENDENCY: &str = "serde = \"1.0\"";
+
+/// Copies the user program into the RISC0 workspace and generates its guest and host programs.
+/// Returns the source maps of the generated files back to the user project.
+pub fn prepare(
+    guest_path: &Path,
+    home_dir: &Path,
+    host_input: HostInput,
+) -> io::Result<SourceMaps> {
+    lint::reject_misplaced_io_calls(guest_path)?;
+    utils::prepare_workspace(
+        guest_path,
+        &home_dir.join(RISC0_SRC_DIR),
+        &home_dir.join(RISC0_GUEST_CARGO_TOML),
+        &home_dir.join(RISC0_HOST_DIR),
+        &home_dir.join(RISC0_HOST_CARGO_TOML),
+        &home_dir.join(RISC0_BASE_HOST_CARGO_TOML),
+        &home_dir.join(RISC0_BASE_GUEST_CARGO_TOML),
+    )?;
+    manifest::apply_project_metadata(
+        guest_path,
+        Backend::Risc0,
+        &home_dir.join(RISC0_GUEST_CARGO_TOML),
+    )?;
+    utils::add_dependency(
+        &home_dir.join(RISC0_HOST_CARGO_TOML),
+        "serde",
+        RISC0_HOST_SERDE_DEPENDENCY,
+    )?;
+
+    let Ok(imports) = utils::get_imports(&home_dir.join(RISC0_GUEST_MAIN)) else {
+        return Err(io::Error::other("Failed to extract imports"));
+    };
+    let main_path = home_dir.join(RISC0_GUEST_MAIN);
+    let Ok(function_bodies) = utils::extract_function_bodies(
+        &main_path,
+        vec![
+            "fn main()".to_string(),
+            "fn input()".to_string(),
+            "fn output()".to_string(),
+        ],
+    ) else {
+        return Err(io::Error::other("Failed to extract function bodies"));
+    };
+
+    /*
+        Adds header to the guest & replace I/O imports
+        risc0:
+
+            #![no_main]
+            risc0_zkvm::guest::entry!(main);
+    */
+    let original_main = guest_path.join("src/main.rs");
+    // Public inputs are tagged in `input()`, values of an input file are all private
+    let public_inputs =
+        host_input == HostInput::Function && utils::uses_public_inputs(&function_bodies[1]);
+    let (program_header, io_read) = if public_inputs {
+        (RISC0_GUEST_PUBLIC_INPUTS_HEADER, utils::GUEST_TAGGED_READ)
+    } else {
+        (RISC0_GUEST_PROGRAM_HEADER, RISC0_IO_READ)
+    };
+    let guest_map = utils::prepare_guest(
+        &imports,
+        &function_bodies[0],
+        program_header,
+        &utils::GuestIo {
+            read: io_read,
+            read_slice: RISC0_IO_READ_SLICE,
+            commit: RISC0_IO_COMMIT,
+            cycle_count: RISC0_IO_CYCLE_COUNT,
+        },
+        &home_dir.join(RISC0_GUEST_MAIN),
+        &original_main,
+    )?;
+    let input = match host_input {
+        HostInput::Function => function_bodies[1].clone(),
+        HostInput::File => input::host_input_code(&input::guest_reads(&function_bodies[0])?),
+    };
+    let public_inputs_host = public_inputs
+        .then(|| utils::public_inputs_host(home_dir, RISC0_BASE_PUBLIC_INPUTS_HOST))
+        .transpose()?;
+    let host_map = prepare_host(
+        &input,
+        &function_bodies[2],
+        &imports,
+        &home_dir.join(RISC0_BASE_HOST),
+        &home_dir.join(RISC0_HOST_MAIN),
+        &original_main,
+        public_inputs_host.as_deref(),
+    )?;
+    if host_input == HostInput::File {
+        input::add_input_loader(
+            home_dir,
+            &home_dir.join(RISC0_HOST_MAIN),
+            &home_dir.join(RISC0_HOST_CARGO_TOML),
+        )?;
+    }
+    if utils::uses_cycle_tracking(&function_bodies[0]) {
+        let mut guest_main = OpenOptions::new()
+            .append(true)
+            .open(home_dir.join(RISC0_GUEST_MAIN))?;
+        guest_main.write_all(
+            fs::read_to_string(home_dir.join(RISC0_BASE_CYCLE_TRACKER_GUEST))?.as_bytes(),
+        )?;
+    }
+    if public_inputs {
+        let mut guest_main = OpenOptions::new()
+            .append(true)
+            .open(home_dir.join(RISC0_GUEST_MAIN))?;
+        guest_main.write_all(
+            fs::read_to_string(home_dir.join(RISC0_BASE_PUBLIC_INPUTS_GUEST))?.as_bytes(),
+        )?;
+        utils::add_dependency(
+            &home_dir.join(RISC0_GUEST_CARGO_TOML),
+            "serde",
+            utils::SERDE_DEPENDENCY,
+        )?;
+        utils::add_dependency(
+            &home_dir.join(RISC0_HOST_CARGO_TOML),
+            "serde_json",
+            input::SERDE_JSON_DEPENDENCY,
+        )?;
+    }
+
+    let mut source_maps = SourceMaps::default();
+    source_maps.add_file(guest_map);
+    source_maps.add_file(host_map);
+    for workspace_dir in [RISC0_SRC_DIR, RISC0_HOST_DIR] {
+        for dir in ["src", "lib"] {
+            source_maps.add_dir(
+                &home_dir.join(workspace_dir).join(dir),
+                &guest_path.join(dir),
+            );
+        }
+    }
+    Ok(source_maps)
+}
 
 pub fn prepare_host(
     input: &str,
@@ -48,52 +228,62 @@ pub fn prepare_host(
     imports: &str,
     host_dir: &PathBuf,
     host_main: &PathBuf,
-) -> io::Result<()> {
+    original_main: &Path,
+    public_inputs_host: Option<&str>,
+) -> io::Result<SourceMap> {
     let mut host_program = imports.to_string();
     let contents = fs::read_to_string(host_dir)?;
     host_program.push_str(&contents);
+    if let Some(public_inputs_host) = public_inputs_host {
+        host_program = utils::add_host_public_inputs(&host_program, public_inputs_host, "risc0");
+    }
 
     // Insert input body
     let host_program = host_program.replace(utils::HOST_INPUT, input);
     // Insert output body
     let host_program = host_program.replace(utils::HOST_OUTPUT, output);
 
-    // Extract Variable names from host and add them to the ExecutorEnv::builder()
-    let values = utils::extract_regex(
-        host_main,
-        &format!("{}[(](.*?)[)]", regex::escape(utils::IO_WRITE)),
-    )?;
+    // Lines are only moved before the I/O replacements below, which keep line numbers intact.
+    let original = fs::read_to_string(original_main)?;
+    let mut source_map = SourceMap::new(host_main, original_main);
+    source_map.map_lines(&host_program, &original, imports);
+    source_map.map_snippet(&host_program, &original, input);
+    source_map.map_snippet(&host_program, &original, output);
 
-    // Construct new Environment Builder
-    let mut new_builder = RISC0_ENV_BUILDER.to_string();
-    for value in values {
-        new_builder.push_str(&format!(".write({}).unwrap()", value));
-    }
-    new_builder.push_str(".build().unwrap();");
+    let host_program = utils::route_host_writes(&host_program, public_inputs_host.is_some());
 
-    // Replace environment builder in host with new one
-    let host_program = host_program.replace(
-        "let env = ExecutorEnv::builder().build().unwrap();",
-        &new_builder,
-    );
+    // replace zkRust::write
+    let host_program = host_program.replace(utils::IO_WRITE, RISC0_HOST_WRITE);
 
     // replace zkRust::out()
     let host_program = host_program.replace(utils::IO_OUT, RISC0_IO_OUT);
+    // replace zkRust::host_args()
+    let host_program = host_program.replace(utils::IO_HOST_ARGS, utils::HOST_ARGS);
+    // replace zkRust::project_dir()
+    let host_program = host_program.replace(utils::IO_PROJECT_DIR, utils::HOST_PROJECT_DIR);
 
     let mut file = fs::File::create(host_main)?;
     file.write_all(host_program.as_bytes())?;
 
-    utils::remove_lines(host_main, "zk_rust_io::write(")?;
-    Ok(())
+    Ok(source_map)
 }
 
 /// Generates RISC0 proof and image ID
-pub fn generate_risc0_proof(guest_path: &PathBuf, current_dir: &PathBuf) -> io::Result<ExitStatus> {
+pub fn generate_risc0_proof(
+    guest_path: &PathBuf,
+    current_dir: &PathBuf,
+    stdout: Stdio,
+    envs: &[(&str, String)],
+    host_args: &[String],
+) -> io::Result<ExitStatus> {
     Command::new("cargo")
         .arg("run")
         .arg("--release")
         .arg("--")
         .arg(current_dir)
+        .args(host_args)
         .current_dir(guest_path)
+        .envs(envs.iter().cloned())
+        .stdout(stdout)
         .status()
 }
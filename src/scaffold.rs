@@ -0,0 +1,284 @@
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// `zk_rust_io` dependency added to every generated project
+pub const ZK_RUST_IO_DEPENDENCY: &str =
+    "zk_rust_io = { git = \"https://github.com/yetanotherco/zkRust.git\" }";
+pub const LIB_DEPENDENCY: &str = "lib = { path = \"lib\" }";
+
+/// Program used when no template is selected
+const DEFAULT_MAIN: &str = r#"use zk_rust_io;
+
+// Code proven inside the zkVM.
+fn main() {
+    let n: u32 = zk_rust_io::read();
+    zk_rust_io::commit(&n);
+}
+
+// Runs on the host before proving. Writes the values read by `main()`.
+fn input() {
+    let n = 10u32;
+    zk_rust_io::write(&n);
+}
+
+// Runs on the host after proving. Reads the values committed by `main()`.
+fn output() {
+    let n: u32 = zk_rust_io::out();
+    println!("n: {}", n);
+}
+"#;
+
+const LIB_CARGO_TOML: &str = r#"[package]
+name = "lib"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+"#;
+
+const LIB_RS: &str = "// Code shared between the zkVM program and the host.\n";
+
+const GITIGNORE: &str = "target/\nproof_data/\naligned_verification_data/\n";
+
+#[derive(Args, Debug)]
+pub struct NewArgs {
+    /// Directory of the new project, its name is used as the package name
+    pub path: PathBuf,
+    #[clap(long = "template", value_enum, help = "Example program to start from")]
+    pub template: Option<Template>,
+    #[clap(
+        long = "lib",
+        help = "Adds a `lib/` crate shared by the program and the host"
+    )]
+    pub lib: bool,
+}
+
+/// Example programs that can be used as project templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Template {
+    Fibonacci,
+    Json,
+    Rsa,
+    Ecdsa,
+    Regex,
+    Sha,
+    Tendermint,
+    Zkquiz,
+}
+
+impl Template {
+    /// Cargo.toml of the example, its `[dependencies]` are reused by the new project.
+    fn cargo_toml(&self) -> &'static str {
+        match self {
+            Template::Fibonacci => include_str!("../examples/fibonacci/Cargo.toml"),
+            Template::Json => include_str!("../examples/json/Cargo.toml"),
+            Template::Rsa => include_str!("../examples/rsa/Cargo.toml"),
+            Template::Ecdsa => include_str!("../examples/ecdsa/Cargo.toml"),
+            Template::Regex => include_str!("../examples/regex/Cargo.toml"),
+            Template::Sha => include_str!("../examples/sha/Cargo.toml"),
+            Template::Tendermint => include_str!("../examples/tendermint/Cargo.toml"),
+            Template::Zkquiz => include_str!("../examples/zkquiz/Cargo.toml"),
+        }
+    }
+
+    /// Source files of the example, relative to the project root.
+    fn files(&self) -> Vec<(&'static str, &'static [u8])> {
+        match self {
+            Template::Fibonacci => vec![(
+                "src/main.rs",
+                include_bytes!("../examples/fibonacci/src/main.rs"),
+            )],
+            Template::Json => vec![
+                (
+                    "src/main.rs",
+                    include_bytes!("../examples/json/src/main.rs"),
+                ),
+                (
+                    "lib/Cargo.toml",
+                    include_bytes!("../examples/json/lib/Cargo.toml"),
+                ),
+                (
+                    "lib/src/lib.rs",
+                    include_bytes!("../examples/json/lib/src/lib.rs"),
+                ),
+            ],
+            Template::Rsa => vec![
+                ("src/main.rs", include_bytes!("../examples/rsa/src/main.rs")),
+                (
+                    "src/rsa2048-priv.der",
+                    include_bytes!("../examples/rsa/src/rsa2048-priv.der"),
+                ),
+                (
+                    "src/rsa2048-pub.der",
+                    include_bytes!("../examples/rsa/src/rsa2048-pub.der"),
+                ),
+            ],
+            Template::Ecdsa => vec![(
+                "src/main.rs",
+                include_bytes!("../examples/ecdsa/src/main.rs"),
+            )],
+            Template::Regex => vec![(
+                "src/main.rs",
+                include_bytes!("../examples/regex/src/main.rs"),
+            )],
+            Template::Sha => vec![("src/main.rs", include_bytes!("../examples/sha/src/main.rs"))],
+            Template::Tendermint => vec![
+                (
+                    "src/main.rs",
+                    include_bytes!("../examples/tendermint/src/main.rs"),
+                ),
+                (
+                    "src/utils.rs",
+                    include_bytes!("../examples/tendermint/src/utils.rs"),
+                ),
+                (
+                    "src/files/block_2279100.json",
+                    include_bytes!("../examples/tendermint/src/files/block_2279100.json"),
+                ),
+                (
+                    "src/files/block_2279130.json",
+                    include_bytes!("../examples/tendermint/src/files/block_2279130.json"),
+                ),
+            ],
+            Template::Zkquiz => vec![
+                (
+                    "src/main.rs",
+                    include_bytes!("../examples/zkquiz/src/main.rs"),
+                ),
+                (
+                    "src/utils.rs",
+                    include_bytes!("../examples/zkquiz/src/utils.rs"),
+                ),
+            ],
+        }
+    }
+}
+
+/// Result of the `new` command.
+#[derive(Debug, Serialize)]
+pub struct NewProjectOutput {
+    pub name: String,
+    pub path: PathBuf,
+    pub template: Option<Template>,
+    pub lib: bool,
+}
+
+/// Creates a zkRust project at `project_dir` with `main()`, `input()` and `output()` defined,
+/// either from one of the examples or from a minimal stub.
+pub fn new_project(
+    project_dir: &Path,
+    template: Option<Template>,
+    with_lib: bool,
+) -> io::Result<NewProjectOutput> {
+    let Some(name) = project_dir.file_name().and_then(|name| name.to_str()) else {
+        return Err(io::Error::other("Invalid project path"));
+    };
+    if !is_valid_package_name(name) {
+        return Err(io::Error::other(format!(
+            "`{}` is not a valid package name, use only letters, numbers, `-` and `_`",
+            name
+        )));
+    }
+    if project_dir.exists() && fs::read_dir(project_dir)?.next().is_some() {
+        return Err(io::Error::other(format!(
+            "Destination {:?} already exists and is not empty",
+            project_dir
+        )));
+    }
+
+    let files = match template {
+        Some(template) => template.files(),
+        None => vec![("src/main.rs", DEFAULT_MAIN.as_bytes())],
+    };
+    let dependencies = match template {
+        Some(template) => template_dependencies(template.cargo_toml()),
+        None => String::new(),
+    };
+    // The json example already ships its `lib/` crate.
+    let with_lib = with_lib || files.iter().any(|(path, _)| path.starts_with("lib/"));
+
+    for (path, contents) in files {
+        let file_path = project_dir.join(path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if path == "src/main.rs" {
+            fs::write(
+                file_path,
+                with_host_stubs(&String::from_utf8_lossy(contents)),
+            )?;
+        } else {
+            fs::write(file_path, contents)?;
+        }
+    }
+    if with_lib && !project_dir.join("lib").exists() {
+        fs::create_dir_all(project_dir.join("lib/src"))?;
+        fs::write(project_dir.join("lib/Cargo.toml"), LIB_CARGO_TOML)?;
+        fs::write(project_dir.join("lib/src/lib.rs"), LIB_RS)?;
+    }
+
+    fs::write(
+        project_dir.join("Cargo.toml"),
+        cargo_toml(name, &dependencies, with_lib),
+    )?;
+    fs::write(project_dir.join(".gitignore"), GITIGNORE)?;
+
+    Ok(NewProjectOutput {
+        name: name.to_string(),
+        path: project_dir.to_path_buf(),
+        template,
+        lib: with_lib,
+    })
+}
+
+fn is_valid_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+}
+
+// Some examples only define `main()`, zkRust expects `input()` and `output()` to be present.
+fn with_host_stubs(main_rs: &str) -> String {
+    let mut main_rs = main_rs.to_string();
+    if !main_rs.contains("fn input()") {
+        main_rs.push_str("\nfn input() {}\n");
+    }
+    if !main_rs.contains("fn output()") {
+        main_rs.push_str("\nfn output() {}\n");
+    }
+    main_rs
+}
+
+// Everything after `[dependencies]` in the example manifest.
+fn template_dependencies(cargo_toml: &str) -> String {
+    match cargo_toml.find("[dependencies]") {
+        Some(index) => cargo_toml[index + "[dependencies]".len()..]
+            .trim()
+            .to_string(),
+        None => String::new(),
+    }
+}
+
+fn cargo_toml(name: &str, dependencies: &str, with_lib: bool) -> String {
+    let mut dependencies = dependencies.to_string();
+    if !dependencies.contains("zk_rust_io") {
+        dependencies.push('\n');
+        dependencies.push_str(ZK_RUST_IO_DEPENDENCY);
+    }
+    if with_lib && !dependencies.contains(LIB_DEPENDENCY) {
+        dependencies.push('\n');
+        dependencies.push_str(LIB_DEPENDENCY);
+    }
+    format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n{}\n",
+        name,
+        dependencies.trim()
+    )
+}
@@ -0,0 +1,235 @@
+use aligned_sdk::core::errors::SubmitError;
+use ethers::signers::{coins_bip39::English, LocalWallet, MnemonicBuilder};
+use serde::Deserialize;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+/// First account of a mnemonic, as derived by most Ethereum wallets.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+const SIGNER_FORMS: &str = "keystore, private-key-env:<VAR>, private-key-file:<PATH>, mnemonic-env:<VAR>, mnemonic-file:<PATH> or private-key-command:<COMMAND>";
+
+/// Where the key of the wallet paying for proof submission comes from, selected with `--signer`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SignerSource {
+    /// Encrypted keystore of `--keystore-path`, unlocked with a password prompt
+    #[default]
+    Keystore,
+    /// Environment variable holding a hex private key
+    PrivateKeyEnv(String),
+    /// File holding a hex private key
+    PrivateKeyFile(PathBuf),
+    /// Environment variable holding a mnemonic, derived with `--derivation-path`
+    MnemonicEnv(String),
+    /// File holding a mnemonic, derived with `--derivation-path`
+    MnemonicFile(PathBuf),
+    /// Command run with `sh -c` that exports a hex private key on stdout, e.g. the CLI of a
+    /// password manager. Its stdin and stderr are the ones of zkRust so it can prompt for an
+    /// unlock.
+    PrivateKeyCommand(String),
+}
+
+impl FromStr for SignerSource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, String> {
+        if source == "keystore" {
+            return Ok(SignerSource::Keystore);
+        }
+        let invalid = || format!("Invalid signer `{}`, expected {}", source, SIGNER_FORMS);
+        let (kind, value) = source
+            .split_once(':')
+            .filter(|(_, value)| !value.is_empty())
+            .ok_or_else(invalid)?;
+        match kind {
+            "private-key-env" => Ok(SignerSource::PrivateKeyEnv(value.to_string())),
+            "private-key-file" => Ok(SignerSource::PrivateKeyFile(PathBuf::from(value))),
+            "mnemonic-env" => Ok(SignerSource::MnemonicEnv(value.to_string())),
+            "mnemonic-file" => Ok(SignerSource::MnemonicFile(PathBuf::from(value))),
+            "private-key-command" => Ok(SignerSource::PrivateKeyCommand(value.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for SignerSource {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, String> {
+        source.parse()
+    }
+}
+
+impl fmt::Display for SignerSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignerSource::Keystore => write!(f, "keystore"),
+            SignerSource::PrivateKeyEnv(var) => write!(f, "private-key-env:{}", var),
+            SignerSource::PrivateKeyFile(path) => write!(f, "private-key-file:{}", path.display()),
+            SignerSource::MnemonicEnv(var) => write!(f, "mnemonic-env:{}", var),
+            SignerSource::MnemonicFile(path) => write!(f, "mnemonic-file:{}", path.display()),
+            SignerSource::PrivateKeyCommand(command) => {
+                write!(f, "private-key-command:{}", command)
+            }
+        }
+    }
+}
+
+impl SignerSource {
+    /// Resolves the relative files of the source from `dir`, the one of the zkrust.toml naming it.
+    pub fn relative_to(self, dir: &Path) -> Self {
+        match self {
+            SignerSource::PrivateKeyFile(path) => SignerSource::PrivateKeyFile(dir.join(path)),
+            SignerSource::MnemonicFile(path) => SignerSource::MnemonicFile(dir.join(path)),
+            source => source,
+        }
+    }
+
+    /// Loads the wallet of the source. Keys are never part of the errors.
+    pub fn wallet(
+        &self,
+        keystore_path: Option<&Path>,
+        derivation_path: &str,
+    ) -> Result<LocalWallet, SubmitError> {
+        match self {
+            SignerSource::Keystore => {
+                let Some(keystore_path) = keystore_path else {
+                    return Err(SubmitError::GenericError(
+                        "Keystore path no found. Please supply path to your local wallet keystore."
+                            .to_string(),
+                    ));
+                };
+                let keystore_password = rpassword::prompt_password("Enter keystore password: ")
+                    .map_err(|e| SubmitError::WalletSignerError(e.to_string()))?;
+                LocalWallet::decrypt_keystore(keystore_path, keystore_password)
+                    .map_err(|e| SubmitError::WalletSignerError(e.to_string()))
+            }
+            SignerSource::PrivateKeyEnv(var) => self.private_key(&read_env(var)?),
+            SignerSource::PrivateKeyFile(path) => self.private_key(&read_file(path)?),
+            SignerSource::MnemonicEnv(var) => self.mnemonic(&read_env(var)?, derivation_path),
+            SignerSource::MnemonicFile(path) => self.mnemonic(&read_file(path)?, derivation_path),
+            SignerSource::PrivateKeyCommand(command) => self.private_key(&run_command(command)?),
+        }
+    }
+
+    fn private_key(&self, key: &str) -> Result<LocalWallet, SubmitError> {
+        key.trim().parse().map_err(|_| {
+            SubmitError::WalletSignerError(format!("Invalid hex private key from {}", self))
+        })
+    }
+
+    fn mnemonic(&self, phrase: &str, derivation_path: &str) -> Result<LocalWallet, SubmitError> {
+        MnemonicBuilder::<English>::default()
+            .phrase(phrase.trim())
+            .derivation_path(derivation_path)
+            .and_then(|builder| builder.build())
+            .map_err(|_| {
+                SubmitError::WalletSignerError(format!(
+                    "Invalid mnemonic from {} or derivation path {}",
+                    self, derivation_path
+                ))
+            })
+    }
+}
+
+fn read_env(var: &str) -> Result<String, SubmitError> {
+    std::env::var(var).map_err(|_| {
+        SubmitError::WalletSignerError(format!("Environment variable {} is not set", var))
+    })
+}
+
+fn read_file(path: &Path) -> Result<String, SubmitError> {
+    fs::read_to_string(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))
+}
+
+fn run_command(command: &str) -> Result<String, SubmitError> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| {
+            SubmitError::WalletSignerError(format!("Failed to run private key command: {}", e))
+        })?;
+    if !output.status.success() {
+        return Err(SubmitError::WalletSignerError(format!(
+            "Private key command failed with {}",
+            output.status
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|_| {
+        SubmitError::WalletSignerError("Private key command printed an invalid key".to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_forms_it_displays() {
+        let sources = [
+            SignerSource::Keystore,
+            SignerSource::PrivateKeyEnv("DEVNET_KEY".to_string()),
+            SignerSource::PrivateKeyFile(PathBuf::from("keys/devnet")),
+            SignerSource::MnemonicEnv("DEVNET_MNEMONIC".to_string()),
+            SignerSource::MnemonicFile(PathBuf::from("/run/secrets/mnemonic")),
+            SignerSource::PrivateKeyCommand("pass show aligned:key".to_string()),
+        ];
+        for source in sources {
+            assert_eq!(source.to_string().parse::<SignerSource>(), Ok(source));
+        }
+        assert_eq!(
+            "private-key-command:op read op://dev/key".parse::<SignerSource>(),
+            Ok(SignerSource::PrivateKeyCommand(
+                "op read op://dev/key".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_forms() {
+        for source in [
+            "",
+            "keystore:",
+            "private-key-env:",
+            "command:pass show key",
+            "ledger",
+        ] {
+            let error = source.parse::<SignerSource>().unwrap_err();
+            assert!(error.contains(SIGNER_FORMS), "{}", error);
+        }
+    }
+
+    #[test]
+    fn resolves_files_from_the_config_dir() {
+        let dir = Path::new("/project");
+        assert_eq!(
+            SignerSource::PrivateKeyFile(PathBuf::from("keys/dev")).relative_to(dir),
+            SignerSource::PrivateKeyFile(PathBuf::from("/project/keys/dev"))
+        );
+        assert_eq!(
+            SignerSource::MnemonicFile(PathBuf::from("/run/mnemonic")).relative_to(dir),
+            SignerSource::MnemonicFile(PathBuf::from("/run/mnemonic"))
+        );
+        assert_eq!(
+            SignerSource::PrivateKeyCommand("cat key".to_string()).relative_to(dir),
+            SignerSource::PrivateKeyCommand("cat key".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_the_key_exported_by_a_command() {
+        let key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        let source = SignerSource::PrivateKeyCommand(format!("echo {}", key));
+        assert!(source.wallet(None, DEFAULT_DERIVATION_PATH).is_ok());
+        let failing = SignerSource::PrivateKeyCommand("exit 3".to_string());
+        assert!(failing.wallet(None, DEFAULT_DERIVATION_PATH).is_err());
+    }
+}
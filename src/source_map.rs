@@ -0,0 +1,597 @@
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+};
+
+use crate::output::OutputFormat;
+
+/// Maps the lines of a file generated by zkRust back to the user's source file.
+/// Generation only moves whole lines around, so a line number and a column offset per line is enough.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    pub generated: PathBuf,
+    pub original: PathBuf,
+    // generated line -> (original line, column delta), lines are 1-based
+    lines: HashMap<usize, (usize, isize)>,
+}
+
+impl SourceMap {
+    pub fn new(generated: &Path, original: &Path) -> Self {
+        SourceMap {
+            generated: generated.to_path_buf(),
+            original: original.to_path_buf(),
+            lines: HashMap::new(),
+        }
+    }
+
+    /// Maps a block of code copied verbatim from `original` into `generated`.
+    /// Only the first line of the block may have lost its indentation.
+    pub fn map_snippet(&mut self, generated: &str, original: &str, snippet: &str) {
+        if snippet.is_empty() {
+            return;
+        }
+        let (Some(generated_index), Some(original_index)) =
+            (generated.find(snippet), original.find(snippet))
+        else {
+            return;
+        };
+        let (generated_line, generated_column) = position(generated, generated_index);
+        let (original_line, original_column) = position(original, original_index);
+        for offset in 0..snippet.lines().count() {
+            let column_delta = if offset == 0 {
+                original_column as isize - generated_column as isize
+            } else {
+                0
+            };
+            self.lines.insert(
+                generated_line + offset,
+                (original_line + offset, column_delta),
+            );
+        }
+    }
+
+    /// Maps lines that keep their relative order in both files but are not contiguous, like imports.
+    pub fn map_lines(&mut self, generated: &str, original: &str, lines: &str) {
+        let (mut generated_cursor, mut original_cursor) = (0, 0);
+        for line in lines.lines().filter(|line| !line.trim().is_empty()) {
+            let (Some(generated_index), Some(original_index)) = (
+                generated[generated_cursor..].find(line),
+                original[original_cursor..].find(line),
+            ) else {
+                continue;
+            };
+            generated_cursor += generated_index;
+            original_cursor += original_index;
+            let (generated_line, _) = position(generated, generated_cursor);
+            let (original_line, _) = position(original, original_cursor);
+            self.lines.insert(generated_line, (original_line, 0));
+            generated_cursor += line.len();
+            original_cursor += line.len();
+        }
+    }
+
+    /// Original line and column of a position in the generated file. The column only accounts
+    /// for the indentation of the line, see `align_column` for lines whose code was rewritten.
+    pub fn lookup(&self, line: usize, column: usize) -> Option<(usize, usize)> {
+        self.lines.get(&line).map(|(original_line, column_delta)| {
+            (
+                *original_line,
+                (column as isize + column_delta).max(1) as usize,
+            )
+        })
+    }
+}
+
+// 1-based line and column of a byte index
+fn position(text: &str, index: usize) -> (usize, usize) {
+    let before = &text[..index];
+    let line = before.matches('\n').count() + 1;
+    let column = index - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+/// Column of `original_line` holding the character at `column` of `generated_line`, columns are
+/// 1-based and count characters like rustc does. The lines only differ by their indentation and
+/// the I/O calls zkRust replaced, characters of a replaced call map to the start of the call.
+pub fn align_column(generated_line: &str, original_line: &str, column: usize) -> usize {
+    let generated: Vec<char> = generated_line.chars().collect();
+    let original: Vec<char> = original_line.chars().collect();
+    // common[i][j] is the length of the longest common subsequence of generated[i..] and original[j..]
+    let mut common = vec![vec![0usize; original.len() + 1]; generated.len() + 1];
+    for i in (0..generated.len()).rev() {
+        for j in (0..original.len()).rev() {
+            common[i][j] = if generated[i] == original[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < generated.len() && i + 1 < column {
+        if j < original.len() && generated[i] == original[j] {
+            i += 1;
+            j += 1;
+        } else if j < original.len() && common[i][j + 1] >= common[i + 1][j] {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+    // Original characters missing from the generated line come before the character at `column`
+    while i < generated.len()
+        && j < original.len()
+        && generated[i] != original[j]
+        && common[i][j + 1] > common[i + 1][j]
+    {
+        j += 1;
+    }
+    // Past the end of the generated line, e.g. the end of a span covering the whole line
+    j + 1 + column.saturating_sub(generated.len() + 1)
+}
+
+// Text of a 1-based line of a file
+fn read_line(path: &Path, line: usize) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()?
+        .lines()
+        .nth(line.checked_sub(1)?)
+        .map(str::to_string)
+}
+
+/// Source maps of a prepared workspace. Files of the user project that are copied unmodified
+/// into the workspace (other modules, `lib/`) are mapped by directory.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMaps {
+    files: Vec<SourceMap>,
+    dirs: Vec<(PathBuf, PathBuf)>,
+}
+
+impl SourceMaps {
+    pub fn add_file(&mut self, source_map: SourceMap) {
+        self.files.push(source_map);
+    }
+
+    pub fn add_dir(&mut self, generated: &Path, original: &Path) {
+        self.dirs
+            .push((generated.to_path_buf(), original.to_path_buf()));
+    }
+
+    /// Location in the user project of a position in a generated file.
+    pub fn lookup(
+        &self,
+        file: &Path,
+        line: usize,
+        column: usize,
+    ) -> Option<(PathBuf, usize, usize)> {
+        let file = file.canonicalize().ok()?;
+        for source_map in &self.files {
+            if source_map.generated.canonicalize().ok().as_ref() == Some(&file) {
+                let (original_line, indented_column) = source_map.lookup(line, column)?;
+                // The generated file is read before zkRust restores the templates it was made of
+                let original_column = match (
+                    read_line(&source_map.generated, line),
+                    read_line(&source_map.original, original_line),
+                ) {
+                    (Some(generated_text), Some(original_text)) => {
+                        align_column(&generated_text, &original_text, column)
+                    }
+                    _ => indented_column,
+                };
+                return Some((source_map.original.clone(), original_line, original_column));
+            }
+        }
+        for (generated, original) in &self.dirs {
+            if let Some(relative) = generated
+                .canonicalize()
+                .ok()
+                .and_then(|generated| file.strip_prefix(generated).ok().map(Path::to_path_buf))
+            {
+                return Some((original.join(relative), line, column));
+            }
+        }
+        None
+    }
+}
+
+/// Builds the host of a generated workspace and reports the diagnostics of the build pointing
+/// at the user's sources. Returns the number of errors found.
+///
+/// The guest is built by the build script of the host, which only prints its output as text
+/// when it fails. The guest is then checked on its own to report its errors as diagnostics.
+pub fn build_workspace(
+    host_dir: &Path,
+    envs: &[(&str, String)],
+    guest_dir: &Path,
+    guest_toolchain_target: (&str, &str),
+    source_maps: &SourceMaps,
+    output_format: OutputFormat,
+) -> io::Result<usize> {
+    let mut command = Command::new("cargo");
+    command
+        .arg("build")
+        .arg("--release")
+        .arg("--message-format=json")
+        .current_dir(host_dir)
+        .envs(envs.iter().cloned());
+    let build = run_cargo(command, host_dir, source_maps, output_format)?;
+    if build.status.success() || build.errors > 0 {
+        return Ok(build.errors);
+    }
+    if !build.build_script_output.is_empty() {
+        let errors = check_crate(
+            guest_dir,
+            Some(guest_toolchain_target),
+            source_maps,
+            output_format,
+        )?;
+        if errors > 0 {
+            return Ok(errors);
+        }
+    }
+    // Not a compile error of the program, e.g. a dependency that failed to download
+    for line in &build.build_script_output {
+        eprintln!("{}", line);
+    }
+    Err(io::Error::other("Failed to build the generated workspace"))
+}
+
+/// Runs `cargo check` on a generated crate and reports its diagnostics pointing at the user's sources.
+/// Returns the number of errors found.
+pub fn check_crate(
+    crate_dir: &Path,
+    toolchain_target: Option<(&str, &str)>,
+    source_maps: &SourceMaps,
+    output_format: OutputFormat,
+) -> io::Result<usize> {
+    let mut command = Command::new("cargo");
+    if let Some((toolchain, _)) = toolchain_target {
+        command.arg(format!("+{}", toolchain));
+    }
+    command.arg("check").arg("--message-format=json");
+    if let Some((_, target)) = toolchain_target {
+        command.arg("--target").arg(target);
+    }
+    command.current_dir(crate_dir);
+    Ok(run_cargo(command, crate_dir, source_maps, output_format)?.errors)
+}
+
+// Cargo prints this before the output of a failed build script
+const BUILD_SCRIPT_FAILED: &str = "error: failed to run custom build command";
+
+struct CargoRun {
+    status: ExitStatus,
+    errors: usize,
+    // Output of a failed build script, held back from stderr
+    build_script_output: Vec<String>,
+}
+
+// Runs a cargo command with `--message-format=json`, printing the diagnostics of rustc mapped to
+// the user's sources. The progress cargo writes to stderr is forwarded as is.
+fn run_cargo(
+    mut command: Command,
+    crate_dir: &Path,
+    source_maps: &SourceMaps,
+    output_format: OutputFormat,
+) -> io::Result<CargoRun> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stderr = child.stderr.take();
+    let stderr_forwarder = thread::spawn(move || {
+        let mut build_script_output = vec![];
+        let Some(stderr) = stderr else {
+            return build_script_output;
+        };
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if !build_script_output.is_empty() || line.starts_with(BUILD_SCRIPT_FAILED) {
+                build_script_output.push(line);
+            } else {
+                eprintln!("{}", line);
+            }
+        }
+        build_script_output
+    });
+
+    let mut errors = 0;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(mut message) = serde_json::from_str::<Value>(&line?) else {
+                continue;
+            };
+            if message["reason"] != "compiler-message" {
+                continue;
+            }
+            let diagnostic = &mut message["message"];
+            if diagnostic["level"] == "error"
+                && !diagnostic["message"]
+                    .as_str()
+                    .is_some_and(|text| text.starts_with("aborting due to"))
+            {
+                errors += 1;
+            }
+            if remap_diagnostic(diagnostic, crate_dir, source_maps) {
+                diagnostic["rendered"] = Value::from(render(diagnostic));
+            }
+            match output_format {
+                OutputFormat::Human => {
+                    if let Some(rendered) = diagnostic["rendered"].as_str() {
+                        eprint!("{}", rendered);
+                    }
+                }
+                OutputFormat::Json => eprintln!("{}", message),
+            }
+        }
+    }
+    let status = child.wait()?;
+    let build_script_output = stderr_forwarder
+        .join()
+        .map_err(|_| io::Error::other("Failed to read the output of cargo"))?;
+    Ok(CargoRun {
+        status,
+        errors,
+        build_script_output,
+    })
+}
+
+// Rewrites the spans of a rustc diagnostic, and of its children, that point into generated files.
+// Returns whether any span was rewritten.
+fn remap_diagnostic(diagnostic: &mut Value, crate_dir: &Path, source_maps: &SourceMaps) -> bool {
+    let mut remapped = false;
+    if let Some(spans) = diagnostic["spans"].as_array_mut() {
+        for span in spans {
+            remapped |= remap_span(span, crate_dir, source_maps);
+        }
+    }
+    if let Some(children) = diagnostic["children"].as_array_mut() {
+        for child in children {
+            remapped |= remap_diagnostic(child, crate_dir, source_maps);
+        }
+    }
+    remapped
+}
+
+// Points a span at the user's source, with the text of the original lines.
+fn remap_span(span: &mut Value, crate_dir: &Path, source_maps: &SourceMaps) -> bool {
+    let (Some(file), Some(line), Some(column)) = (
+        span["file_name"]
+            .as_str()
+            .and_then(|file_name| resolve(crate_dir, file_name)),
+        span["line_start"].as_u64().map(|line| line as usize),
+        span["column_start"].as_u64().map(|column| column as usize),
+    ) else {
+        return false;
+    };
+    let Some((original, original_line, original_column)) = source_maps.lookup(&file, line, column)
+    else {
+        return false;
+    };
+    let line_end = span["line_end"].as_u64().unwrap_or(line as u64) as usize;
+    let column_end = span["column_end"].as_u64().unwrap_or(column as u64 + 1) as usize;
+    // The end column is exclusive, the last character of the span is mapped
+    let (original_line_end, original_column_end) = source_maps
+        .lookup(&file, line_end, column_end.saturating_sub(1).max(1))
+        .map(|(_, line, column)| (line, column + 1))
+        .unwrap_or((original_line, original_column + 1));
+
+    span["file_name"] = Value::from(original.display().to_string());
+    span["line_start"] = Value::from(original_line);
+    span["line_end"] = Value::from(original_line_end);
+    span["column_start"] = Value::from(original_column);
+    span["column_end"] = Value::from(original_column_end);
+    if let Ok(source) = fs::read_to_string(&original) {
+        let lines: Vec<&str> = source
+            .lines()
+            .skip(original_line - 1)
+            .take(original_line_end.saturating_sub(original_line) + 1)
+            .collect();
+        let last = lines.len().saturating_sub(1);
+        span["text"] = lines
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let indentation = text.len() - text.trim_start().len() + 1;
+                serde_json::json!({
+                    "text": text,
+                    "highlight_start": if index == 0 { original_column } else { indentation },
+                    "highlight_end": if index == last {
+                        original_column_end
+                    } else {
+                        text.chars().count() + 1
+                    },
+                })
+            })
+            .collect();
+    }
+    true
+}
+
+// Renders a diagnostic like rustc, from its spans rather than the snippets rustc rendered out of
+// the generated files.
+fn render(diagnostic: &Value) -> String {
+    format!("{}\n", render_diagnostic(diagnostic))
+}
+
+fn render_diagnostic(diagnostic: &Value) -> String {
+    let spans = diagnostic["spans"].as_array().cloned().unwrap_or_default();
+    let gutter = spans
+        .iter()
+        .filter_map(|span| span["line_end"].as_u64())
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len();
+    let mut rendered = match diagnostic["code"]["code"].as_str() {
+        Some(code) => format!(
+            "{}[{}]: {}\n",
+            diagnostic["level"].as_str().unwrap_or("error"),
+            code,
+            diagnostic["message"].as_str().unwrap_or_default()
+        ),
+        None => format!(
+            "{}: {}\n",
+            diagnostic["level"].as_str().unwrap_or("error"),
+            diagnostic["message"].as_str().unwrap_or_default()
+        ),
+    };
+    if let Some(primary) = spans
+        .iter()
+        .find(|span| span["is_primary"] == true)
+        .or(spans.first())
+    {
+        rendered.push_str(&format!(
+            "{:gutter$}--> {}:{}:{}\n{:gutter$} |\n",
+            "",
+            primary["file_name"].as_str().unwrap_or_default(),
+            primary["line_start"],
+            primary["column_start"],
+            "",
+        ));
+    }
+    // Spans on the line of the previous span only add their markers under it
+    let mut previous_line = None;
+    for span in &spans {
+        let marker = if span["is_primary"] == true { '^' } else { '-' };
+        let line_start = span["line_start"].as_u64().unwrap_or(0);
+        let texts = span["text"].as_array().cloned().unwrap_or_default();
+        for (index, text) in texts.iter().enumerate() {
+            let line = (span["file_name"].as_str(), line_start + index as u64);
+            if previous_line != Some(line) {
+                rendered.push_str(&format!(
+                    "{:>gutter$} | {}\n",
+                    line.1,
+                    text["text"].as_str().unwrap_or_default(),
+                ));
+                previous_line = Some(line);
+            }
+            let start = text["highlight_start"].as_u64().unwrap_or(1).max(1) as usize;
+            let end = text["highlight_end"].as_u64().unwrap_or(1) as usize;
+            rendered.push_str(&format!(
+                "{:gutter$} | {}{}",
+                "",
+                " ".repeat(start - 1),
+                marker.to_string().repeat(end.saturating_sub(start).max(1)),
+            ));
+            match span["label"].as_str() {
+                Some(label) if index + 1 == texts.len() => {
+                    rendered.push_str(&format!(" {}\n", label))
+                }
+                _ => rendered.push('\n'),
+            }
+        }
+    }
+    for child in diagnostic["children"].as_array().into_iter().flatten() {
+        if child["spans"]
+            .as_array()
+            .is_some_and(|spans| !spans.is_empty())
+        {
+            rendered.push_str(&render_diagnostic(child));
+        } else {
+            rendered.push_str(&format!(
+                "{:gutter$} = {}: {}\n",
+                "",
+                child["level"].as_str().unwrap_or("note"),
+                child["message"].as_str().unwrap_or_default()
+            ));
+        }
+    }
+    rendered
+}
+
+// Cargo reports paths relative to the root of the workspace the crate belongs to.
+fn resolve(crate_dir: &Path, file_name: &str) -> Option<PathBuf> {
+    crate_dir
+        .ancestors()
+        .map(|dir| dir.join(file_name))
+        .find(|path| path.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "use std::collections::HashMap;
+
+fn main() {
+    let n: u32 = zk_rust_io::read();
+    let map: HashMap<u32, u32> = HashMap::new();
+    zk_rust_io::commit(&n);
+}
+";
+
+    #[test]
+    fn lookup_maps_moved_snippets_and_imports() {
+        let generated = "#![no_main]
+sp1_zkvm::entrypoint!(main);
+use std::collections::HashMap;
+pub fn main() {
+let n: u32 = zk_rust_io::read();
+    let map: HashMap<u32, u32> = HashMap::new();
+    zk_rust_io::commit(&n);
+}
+";
+        let mut source_map = SourceMap::new(Path::new("generated.rs"), Path::new("main.rs"));
+        source_map.map_lines(generated, ORIGINAL, "use std::collections::HashMap;\n");
+        source_map.map_snippet(
+            generated,
+            ORIGINAL,
+            "let n: u32 = zk_rust_io::read();
+    let map: HashMap<u32, u32> = HashMap::new();
+    zk_rust_io::commit(&n);",
+        );
+
+        assert_eq!(source_map.lookup(3, 5), Some((1, 5)));
+        // The first line of the snippet lost its indentation
+        assert_eq!(source_map.lookup(5, 5), Some((4, 9)));
+        assert_eq!(source_map.lookup(6, 9), Some((5, 9)));
+        assert_eq!(source_map.lookup(7, 5), Some((6, 5)));
+        // Lines of the template are not mapped
+        assert_eq!(source_map.lookup(1, 1), None);
+        assert_eq!(source_map.lookup(8, 1), None);
+    }
+
+    #[test]
+    fn lookup_never_returns_column_zero() {
+        let mut source_map = SourceMap::new(Path::new("generated.rs"), Path::new("main.rs"));
+        source_map.map_snippet("    let a = 1;", "            let a = 1;", "let a = 1;");
+        assert_eq!(source_map.lookup(1, 5), Some((1, 13)));
+
+        let mut source_map = SourceMap::new(Path::new("generated.rs"), Path::new("main.rs"));
+        source_map.map_snippet("        let a = 1;", "let a = 1;", "let a = 1;");
+        assert_eq!(source_map.lookup(1, 3), Some((1, 1)));
+    }
+
+    #[test]
+    fn align_column_skips_replaced_io_calls() {
+        let generated = "    let n: u32 = sp1_zkvm::io::read(); let m = n + 1;";
+        let original = "let n: u32 = zk_rust_io::read(); let m = n + 1;";
+        // `let` before the call
+        assert_eq!(align_column(generated, original, 5), 1);
+        // `n` after the call
+        assert_eq!(align_column(generated, original, 48), 42);
+        // Start of the call
+        assert_eq!(align_column(generated, original, 18), 14);
+        // End of the line, as in the exclusive end of a span
+        assert_eq!(align_column(generated, original, 54), 48);
+    }
+
+    #[test]
+    fn align_column_of_identical_lines() {
+        let line = "    zk_rust_io::commit(&n);";
+        for column in 1..=line.len() + 1 {
+            assert_eq!(align_column(line, line, column), column);
+        }
+    }
+
+    #[test]
+    fn align_column_of_original_only_characters() {
+        assert_eq!(align_column("a = x", "a =  x", 5), 6);
+    }
+}
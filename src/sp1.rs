@@ -1,33 +1,93 @@
 use std::{
-    fs,
+    fs::{self, OpenOptions},
     io::{self, Write},
-    path::PathBuf,
-    process::{Command, ExitStatus},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
 };
 
+use crate::input::{self, HostInput};
+use crate::lint;
+use crate::manifest;
+use crate::precompiles::Patch;
+use crate::source_map::{SourceMap, SourceMaps};
 use crate::utils;
+use crate::Backend;
 
 /// SP1 workspace directories
+pub const SP1_WORKSPACE_DIR: &str = "workspaces/sp1";
 pub const SP1_SCRIPT_DIR: &str = "workspaces/sp1/script";
+pub const SP1_SCRIPT_CARGO_TOML: &str = "workspaces/sp1/script/Cargo.toml";
 pub const SP1_SRC_DIR: &str = "workspaces/sp1/program";
 pub const SP1_GUEST_MAIN: &str = "workspaces/sp1/program/src/main.rs";
+pub const SP1_GUEST_ELF: &str = "workspaces/sp1/program/elf/riscv32im-succinct-zkvm-elf";
 pub const SP1_HOST_MAIN: &str = "workspaces/sp1/script/src/main.rs";
 pub const SP1_BASE_GUEST_CARGO_TOML: &str = "workspaces/base_files/sp1/cargo_guest";
 pub const SP1_BASE_HOST_CARGO_TOML: &str = "workspaces/base_files/sp1/cargo_host";
 pub const SP1_BASE_HOST: &str = "workspaces/base_files/sp1/host";
 pub const SP1_BASE_HOST_FILE: &str = "workspaces/base_files/sp1/host";
+pub const SP1_BASE_PUBLIC_INPUTS_GUEST: &str = "workspaces/base_files/sp1/public_inputs_guest";
+pub const SP1_BASE_PUBLIC_INPUTS_HOST: &str = "workspaces/base_files/sp1/public_inputs_host";
+pub const SP1_BASE_CYCLE_TRACKER_GUEST: &str = "workspaces/base_files/sp1/cycle_tracker_guest";
 pub const SP1_GUEST_CARGO_TOML: &str = "workspaces/sp1/program/Cargo.toml";
 
-// Proof data generation paths
-pub const SP1_ELF_PATH: &str = "./proof_data/sp1/sp1.elf";
-pub const SP1_PROOF_PATH: &str = "./proof_data/sp1/sp1.proof";
-pub const SP1_PUB_INPUT_PATH: &str = "./proof_data/sp1/sp1.pub";
+/// Toolchain and target the SP1 guest is compiled with
+pub const SP1_GUEST_TOOLCHAIN: &str = "succinct";
+pub const SP1_GUEST_TARGET: &str = "riscv32im-succinct-zkvm-elf";
+
+// Proof data generation paths, relative to the `--proof-data-path` directory
+pub const SP1_ELF_PATH: &str = "sp1/sp1.elf";
+pub const SP1_PROOF_PATH: &str = "sp1/sp1.proof";
+pub const SP1_PUB_INPUT_PATH: &str = "sp1/sp1.pub";
+pub const SP1_VKEY_PATH: &str = "sp1/sp1.vkey";
+pub const SP1_CYCLES_PATH: &str = "sp1/sp1.cycles";
+pub const SP1_EXECUTE_CYCLES_PATH: &str = "sp1/sp1.execute_cycles";
+pub const SP1_PUBLIC_INPUTS_PATH: &str = "sp1/sp1.public_inputs.json";
 
 /// SP1 header added to programs for generating proofs of their execution
 pub const SP1_GUEST_PROGRAM_HEADER: &str = "#![no_main]\nsp1_zkvm::entrypoint!(main);\n";
+/// SP1 header of programs with public inputs, `zkrust_main` commits them after running `main()`
+pub const SP1_GUEST_PUBLIC_INPUTS_HEADER: &str =
+    "#![no_main]\nsp1_zkvm::entrypoint!(zkrust_main);\n";
 
-/// SP1 Cargo patch for accelerated SHA-256, K256, and bigint-multiplication circuits
-pub const SP1_ACCELERATION_IMPORT: &str = "\n[patch.crates-io]\nsha2 = { git = \"https://github.com/sp1-patches/RustCrypto-hashes\", package = \"sha2\", branch = \"patch-sha2-v0.10.6\" }\nsha3 = { git = \"https://github.com/sp1-patches/RustCrypto-hashes\", package = \"sha3\", branch = \"patch-sha3-v0.10.8\" }\ncrypto-bigint = { git = \"https://github.com/sp1-patches/RustCrypto-bigint\", branch = \"patch-v0.5.5\" }\ntiny-keccak = { git = \"https://github.com/sp1-patches/tiny-keccak\", branch = \"patch-v2.0.2\" }\ned25519-consensus = { git = \"https://github.com/sp1-patches/ed25519-consensus\", branch = \"patch-v2.1.0\" }\necdsa-core = { git = \"https://github.com/sp1-patches/signatures\", package = \"ecdsa\", branch = \"patch-ecdsa-v0.16.9\" }\n";
+/// Crates patched with the precompiles of SP1 v1.0.1
+pub const SP1_PATCHES: &[Patch] = &[
+    Patch {
+        name: "sha2",
+        version: "0.10.6",
+        accelerates: "SHA-256 hashing",
+        source: "{ git = \"https://github.com/sp1-patches/RustCrypto-hashes\", package = \"sha2\", branch = \"patch-sha2-v0.10.6\" }",
+    },
+    Patch {
+        name: "sha3",
+        version: "0.10.8",
+        accelerates: "SHA-3 and Keccak hashing",
+        source: "{ git = \"https://github.com/sp1-patches/RustCrypto-hashes\", package = \"sha3\", branch = \"patch-sha3-v0.10.8\" }",
+    },
+    Patch {
+        name: "crypto-bigint",
+        version: "0.5.5",
+        accelerates: "big integer multiplication",
+        source: "{ git = \"https://github.com/sp1-patches/RustCrypto-bigint\", branch = \"patch-v0.5.5\" }",
+    },
+    Patch {
+        name: "tiny-keccak",
+        version: "2.0.2",
+        accelerates: "Keccak hashing",
+        source: "{ git = \"https://github.com/sp1-patches/tiny-keccak\", branch = \"patch-v2.0.2\" }",
+    },
+    Patch {
+        name: "ed25519-consensus",
+        version: "2.1.0",
+        accelerates: "Ed25519 signature verification",
+        source: "{ git = \"https://github.com/sp1-patches/ed25519-consensus\", branch = \"patch-v2.1.0\" }",
+    },
+    Patch {
+        name: "ecdsa",
+        version: "0.16.9",
+        accelerates: "ECDSA signature verification",
+        source: "{ git = \"https://github.com/sp1-patches/signatures\", package = \"ecdsa\", branch = \"patch-ecdsa-v0.16.9\" }",
+    },
+];
 
 /// SP1 User I/O
 // Host
@@ -36,7 +96,151 @@ pub const SP1_HOST_READ: &str = "proof.public_values.read();";
 
 // Guest
 pub const SP1_IO_READ: &str = "sp1_zkvm::io::read();";
+pub const SP1_IO_READ_SLICE: &str = "sp1_zkvm::io::read_vec();";
 pub const SP1_IO_COMMIT: &str = "sp1_zkvm::io::commit";
+// SP1 guests cannot read the cycle count, `track` is mapped to SP1's cycle tracker instead
+pub const SP1_IO_CYCLE_COUNT: &str = "0u64";
+
+/// Copies the user program into the SP1 workspace and generates its guest and host programs.
+/// Returns the source maps of the generated files back to the user project.
+pub fn prepare(
+    guest_path: &Path,
+    home_dir: &Path,
+    host_input: HostInput,
+) -> io::Result<SourceMaps> {
+    lint::reject_misplaced_io_calls(guest_path)?;
+    utils::prepare_workspace(
+        guest_path,
+        &home_dir.join(SP1_SRC_DIR),
+        &home_dir.join(SP1_GUEST_CARGO_TOML),
+        &home_dir.join(SP1_SCRIPT_DIR),
+        &home_dir.join(SP1_SCRIPT_CARGO_TOML),
+        &home_dir.join(SP1_BASE_HOST_CARGO_TOML),
+        &home_dir.join(SP1_BASE_GUEST_CARGO_TOML),
+    )?;
+    manifest::apply_project_metadata(
+        guest_path,
+        Backend::Sp1,
+        &home_dir.join(SP1_GUEST_CARGO_TOML),
+    )?;
+
+    let Ok(imports) = utils::get_imports(&home_dir.join(SP1_GUEST_MAIN)) else {
+        return Err(io::Error::other("Failed to extract imports"));
+    };
+
+    let main_path = home_dir.join(SP1_GUEST_MAIN);
+    let Ok(function_bodies) = utils::extract_function_bodies(
+        &main_path,
+        vec![
+            "fn main()".to_string(),
+            "fn input()".to_string(),
+            "fn output()".to_string(),
+        ],
+    ) else {
+        return Err(io::Error::other("Failed to extract function bodies"));
+    };
+    /*
+        Adds header to the guest & replace I/O imports
+        risc0:
+
+            #![no_main]
+            sp1_zkvm::entrypoint!(main);
+    */
+    let original_main = guest_path.join("src/main.rs");
+    // Public inputs are tagged in `input()`, values of an input file are all private
+    let public_inputs =
+        host_input == HostInput::Function && utils::uses_public_inputs(&function_bodies[1]);
+    let (program_header, io_read) = if public_inputs {
+        (SP1_GUEST_PUBLIC_INPUTS_HEADER, utils::GUEST_TAGGED_READ)
+    } else {
+        (SP1_GUEST_PROGRAM_HEADER, SP1_IO_READ)
+    };
+    let guest_map = utils::prepare_guest(
+        &imports,
+        &function_bodies[0],
+        program_header,
+        &utils::GuestIo {
+            read: io_read,
+            read_slice: SP1_IO_READ_SLICE,
+            commit: SP1_IO_COMMIT,
+            cycle_count: SP1_IO_CYCLE_COUNT,
+        },
+        &home_dir.join(SP1_GUEST_MAIN),
+        &original_main,
+    )?;
+    let input = match host_input {
+        HostInput::Function => function_bodies[1].clone(),
+        HostInput::File => input::host_input_code(&input::guest_reads(&function_bodies[0])?),
+    };
+    let public_inputs_host = public_inputs
+        .then(|| utils::public_inputs_host(home_dir, SP1_BASE_PUBLIC_INPUTS_HOST))
+        .transpose()?;
+    let host_map = prepare_host(
+        &input,
+        &function_bodies[2],
+        &imports,
+        &home_dir.join(SP1_BASE_HOST),
+        &home_dir.join(SP1_HOST_MAIN),
+        &original_main,
+        public_inputs_host.as_deref(),
+    )?;
+    if host_input == HostInput::File {
+        input::add_input_loader(
+            home_dir,
+            &home_dir.join(SP1_HOST_MAIN),
+            &home_dir.join(SP1_SCRIPT_CARGO_TOML),
+        )?;
+    }
+    if utils::uses_cycle_tracking(&function_bodies[0]) {
+        let mut guest_main = OpenOptions::new()
+            .append(true)
+            .open(home_dir.join(SP1_GUEST_MAIN))?;
+        guest_main.write_all(
+            fs::read_to_string(home_dir.join(SP1_BASE_CYCLE_TRACKER_GUEST))?.as_bytes(),
+        )?;
+    }
+    if public_inputs {
+        let mut guest_main = OpenOptions::new()
+            .append(true)
+            .open(home_dir.join(SP1_GUEST_MAIN))?;
+        guest_main.write_all(
+            fs::read_to_string(home_dir.join(SP1_BASE_PUBLIC_INPUTS_GUEST))?.as_bytes(),
+        )?;
+        utils::add_dependency(
+            &home_dir.join(SP1_GUEST_CARGO_TOML),
+            "serde",
+            utils::SERDE_DEPENDENCY,
+        )?;
+        utils::add_dependency(
+            &home_dir.join(SP1_GUEST_CARGO_TOML),
+            "bincode",
+            utils::BINCODE_DEPENDENCY,
+        )?;
+        utils::add_dependency(
+            &home_dir.join(SP1_SCRIPT_CARGO_TOML),
+            "serde",
+            utils::SERDE_DEPENDENCY,
+        )?;
+        utils::add_dependency(
+            &home_dir.join(SP1_SCRIPT_CARGO_TOML),
+            "serde_json",
+            input::SERDE_JSON_DEPENDENCY,
+        )?;
+    }
+
+    let mut source_maps = SourceMaps::default();
+    source_maps.add_file(guest_map);
+    source_maps.add_file(host_map);
+    for workspace_dir in [SP1_SRC_DIR, SP1_SCRIPT_DIR] {
+        for dir in ["src", "lib"] {
+            source_maps.add_dir(
+                &home_dir.join(workspace_dir).join(dir),
+                &guest_path.join(dir),
+            );
+        }
+    }
+    Ok(source_maps)
+}
 
 pub fn prepare_host(
     input: &str,
@@ -44,35 +248,62 @@ pub fn prepare_host(
     imports: &str,
     host_dir: &PathBuf,
     host_main: &PathBuf,
-) -> io::Result<()> {
+    original_main: &Path,
+    public_inputs_host: Option<&str>,
+) -> io::Result<SourceMap> {
     let mut host_program = imports.to_string();
     let contents = fs::read_to_string(host_dir)?;
 
     host_program.push_str(&contents);
+    if let Some(public_inputs_host) = public_inputs_host {
+        host_program = utils::add_host_public_inputs(&host_program, public_inputs_host, "sp1");
+    }
 
     // Insert input body
     let host_program = host_program.replace(utils::HOST_INPUT, input);
     // Insert output body
     let host_program = host_program.replace(utils::HOST_OUTPUT, output);
 
+    // Lines are only moved before the I/O replacements below, which keep line numbers intact.
+    let original = fs::read_to_string(original_main)?;
+    let mut source_map = SourceMap::new(host_main, original_main);
+    source_map.map_lines(&host_program, &original, imports);
+    source_map.map_snippet(&host_program, &original, input);
+    source_map.map_snippet(&host_program, &original, output);
+
+    let host_program = utils::route_host_writes(&host_program, public_inputs_host.is_some());
+
     // replace zkRust::write
     let host_program = host_program.replace(utils::IO_WRITE, SP1_HOST_WRITE);
     // replace zkRust::out()
     let host_program = host_program.replace(utils::IO_OUT, SP1_HOST_READ);
+    // replace zkRust::host_args()
+    let host_program = host_program.replace(utils::IO_HOST_ARGS, utils::HOST_ARGS);
+    // replace zkRust::project_dir()
+    let host_program = host_program.replace(utils::IO_PROJECT_DIR, utils::HOST_PROJECT_DIR);
 
     // Write to host
     let mut file = fs::File::create(host_main)?;
     file.write_all(host_program.as_bytes())?;
-    Ok(())
+    Ok(source_map)
 }
 
 /// Generates SP1 proof and ELF
-pub fn generate_sp1_proof(script_dir: &PathBuf, current_dir: &PathBuf) -> io::Result<ExitStatus> {
+pub fn generate_sp1_proof(
+    script_dir: &PathBuf,
+    current_dir: &PathBuf,
+    stdout: Stdio,
+    envs: &[(&str, String)],
+    host_args: &[String],
+) -> io::Result<ExitStatus> {
     Command::new("cargo")
         .arg("run")
         .arg("--release")
         .arg("--")
         .arg(current_dir)
+        .args(host_args)
         .current_dir(script_dir)
+        .envs(envs.iter().cloned())
+        .stdout(stdout)
         .status()
-}
\ No newline at end of file
+}
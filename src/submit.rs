@@ -0,0 +1,176 @@
+use clap::Args;
+use log::info;
+use serde::Serialize;
+use sp1_sdk::{SP1Proof, SP1ProofWithPublicValues};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    batch_proofs_on_aligned, wait_for_verification, AlignedProof, AlignedSubmission, Backend,
+    SubmissionArgs,
+};
+
+/// Summaries of `submit` are written to `<aligned verification data dir>/submission_<unix time>.json`.
+pub const SUMMARY_FILE_PREFIX: &str = "submission_";
+
+#[derive(Args, Debug)]
+pub struct SubmitArgs {
+    #[clap(
+        long = "many",
+        num_args = 1..,
+        required = true,
+        help = "Directories of the proofs to submit, e.g. `proof_data/<input>/sp1` or `proof_data/<input>`"
+    )]
+    pub proof_dirs: Vec<PathBuf>,
+    #[command(flatten)]
+    pub aligned: SubmissionArgs,
+}
+
+/// Artifacts of a proof written by a host.
+#[derive(Debug, Serialize)]
+pub struct ProofFiles {
+    pub backend: Backend,
+    pub proof: PathBuf,
+    /// SP1 ELF or RISC0 image ID
+    pub program: PathBuf,
+    pub public_input: Option<PathBuf>,
+}
+
+impl ProofFiles {
+    // Artifacts are named after the backend, as in `proof_data/sp1/sp1.proof`.
+    fn in_dir(dir: &Path, backend: Backend) -> Option<ProofFiles> {
+        let artifact = |extension: &str| dir.join(format!("{}.{}", backend.name(), extension));
+        let proof = artifact("proof");
+        if !proof.exists() {
+            return None;
+        }
+        let program = match backend {
+            Backend::Sp1 => artifact("elf"),
+            Backend::Risc0 => artifact("imageid"),
+        };
+        let public_input = Some(artifact("pub")).filter(|path| path.exists());
+        Some(ProofFiles {
+            backend,
+            proof,
+            program,
+            public_input,
+        })
+    }
+
+    // Proofs Aligned does not verify are rejected before any fee is paid for them.
+    fn read(&self) -> io::Result<AlignedProof> {
+        let proof = AlignedProof::read(
+            &self.proof,
+            &self.program,
+            self.public_input.as_deref(),
+            self.backend.proving_system(),
+        )
+        .map_err(|e| io::Error::other(e.to_string()))?;
+        if self.backend == Backend::Sp1 {
+            let sp1_proof: SP1ProofWithPublicValues =
+                bincode::deserialize(&proof.proof).map_err(|e| {
+                    io::Error::other(format!("Invalid SP1 proof {}: {}", self.proof.display(), e))
+                })?;
+            if !matches!(sp1_proof.proof, SP1Proof::Compressed(_)) {
+                return Err(io::Error::other(format!(
+                    "{} is not a compressed SP1 proof, the only ones Aligned verifies. Prove it again without --proof-type core",
+                    self.proof.display()
+                )));
+            }
+        }
+        Ok(proof)
+    }
+}
+
+/// Proof submitted by `submit`, with the files it was read from.
+#[derive(Debug, Serialize)]
+pub struct SubmittedProof {
+    #[serde(flatten)]
+    pub files: ProofFiles,
+    pub aligned: AlignedSubmission,
+}
+
+/// Result of `submit`.
+#[derive(Debug, Serialize)]
+pub struct SubmitOutput {
+    pub proofs: Vec<SubmittedProof>,
+    /// JSON file listing `proofs`
+    pub summary: PathBuf,
+}
+
+/// Finds the proofs in `dir`, either the artifacts of one backend or its `sp1` and `risc0`
+/// subdirectories, as written for each input of `prove --inputs-dir`.
+pub fn find_proofs(dir: &Path) -> io::Result<Vec<ProofFiles>> {
+    let backends = [Backend::Sp1, Backend::Risc0];
+    let mut proofs: Vec<ProofFiles> = backends
+        .iter()
+        .filter_map(|backend| ProofFiles::in_dir(dir, *backend))
+        .collect();
+    if proofs.is_empty() {
+        proofs = backends
+            .iter()
+            .filter_map(|backend| ProofFiles::in_dir(&dir.join(backend.name()), *backend))
+            .collect();
+    }
+    if proofs.is_empty() {
+        return Err(io::Error::other(format!(
+            "No SP1 or RISC0 proof found in {}",
+            dir.display()
+        )));
+    }
+    Ok(proofs)
+}
+
+/// Submits the proofs of `args.proof_dirs` to Aligned in one call to the batcher.
+pub async fn submit(args: &SubmitArgs) -> io::Result<SubmitOutput> {
+    let mut files = vec![];
+    for dir in &args.proof_dirs {
+        files.extend(find_proofs(dir)?);
+    }
+    let proofs = files
+        .iter()
+        .map(ProofFiles::read)
+        .collect::<io::Result<Vec<_>>>()?;
+    info!(
+        "Submitting {} proof(s) from {} directories",
+        proofs.len(),
+        args.proof_dirs.len()
+    );
+    let submissions = batch_proofs_on_aligned(proofs, &args.aligned)
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let proofs: Vec<SubmittedProof> = files
+        .into_iter()
+        .zip(submissions)
+        .map(|(files, aligned)| SubmittedProof { files, aligned })
+        .collect();
+
+    // Written once the proofs are paid for and batched, their records track the verification
+    let summary = write_summary(
+        Path::new(&args.aligned.batch_inclusion_data_directory_path),
+        &proofs,
+    )?;
+    info!("Submission summary saved {:?}", summary);
+    let records: Vec<PathBuf> = proofs
+        .iter()
+        .map(|proof| proof.aligned.record.clone())
+        .collect();
+    wait_for_verification(&records, &args.aligned)
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(SubmitOutput { proofs, summary })
+}
+
+fn write_summary(dir: &Path, proofs: &[SubmittedProof]) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(io::Error::other)?
+        .as_secs();
+    let path = dir.join(format!("{}{}.json", SUMMARY_FILE_PREFIX, timestamp));
+    fs::write(&path, serde_json::to_vec_pretty(proofs)?)?;
+    Ok(path)
+}
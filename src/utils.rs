@@ -1,5 +1,5 @@
+use crate::source_map::SourceMap;
 use log::error;
-use regex::Regex;
 use std::{
     fs::{self, File, OpenOptions},
     io::{self, BufRead, BufReader, ErrorKind, Read, Seek, Write},
@@ -9,12 +9,44 @@ use std::{
 // Host
 pub const IO_WRITE: &str = "zk_rust_io::write";
 pub const IO_OUT: &str = "zk_rust_io::out();";
+pub const IO_HOST_ARGS: &str = "zk_rust_io::host_args()";
+// The hosts take the proof data directory first, the arguments after `--` follow it.
+pub const HOST_ARGS: &str = "args[2..].to_vec()";
+pub const IO_PROJECT_DIR: &str = "zk_rust_io::project_dir()";
+/// Environment variable holding the project root, the host itself runs inside the workspace.
+pub const PROJECT_DIR_ENV: &str = "ZKRUST_PROJECT_DIR";
+/// Environment variable making the host execute the guest and save its cycles without proving it.
+pub const EXECUTE_ENV: &str = "ZKRUST_EXECUTE";
+/// Environment variable holding the `--proof-data-path` directory the host writes the proofs to.
+pub const PROOF_DATA_DIR_ENV: &str = "ZKRUST_PROOF_DATA_DIR";
+/// Environment variable holding the `--proof-type` of the proofs generated by the host.
+pub const PROOF_TYPE_ENV: &str = "ZKRUST_PROOF_TYPE";
+// Ejected workspaces run without zkRust, the project is then the working directory.
+pub const HOST_PROJECT_DIR: &str =
+    "std::path::PathBuf::from(std::env::var(\"ZKRUST_PROJECT_DIR\").unwrap_or_else(|_| \".\".to_string()))";
 pub const HOST_INPUT: &str = "// INPUT //";
 pub const HOST_OUTPUT: &str = "// OUTPUT //";
+pub const HOST_PUBLIC_INPUTS: &str = "// PUBLIC INPUTS //";
+/// `ZkRustInputs` writer shared by the hosts of every backend, which add their `write_*` methods
+pub const BASE_PUBLIC_INPUTS_HOST: &str = "workspaces/base_files/public_inputs_host";
+
+// Public inputs
+pub const IO_WRITE_PRIVATE: &str = "zk_rust_io::write_private(";
+pub const IO_WRITE_PUBLIC: &str = "zk_rust_io::write_public(";
+pub const HOST_TAGGED_WRITE_PRIVATE: &str = "zkrust_inputs.write_private(&mut stdin, ";
+pub const HOST_TAGGED_WRITE_PUBLIC: &str = "zkrust_inputs.write_public(&mut stdin, ";
+pub const HOST_INPUTS_DECLARATION: &str = "let mut zkrust_inputs = ZkRustInputs::default();";
+pub const GUEST_TAGGED_READ: &str = "zkrust_read();";
+pub const SERDE_DEPENDENCY: &str = "serde = \"1.0\"";
+pub const BINCODE_DEPENDENCY: &str = "bincode = \"1.3.3\"";
 
 // I/O Markers
 pub const IO_READ: &str = "zk_rust_io::read();";
+pub const IO_READ_SLICE: &str = "zk_rust_io::read_slice();";
 pub const IO_COMMIT: &str = "zk_rust_io::commit";
+pub const IO_CYCLE_COUNT: &str = "zk_rust_io::cycle_count()";
+pub const IO_TRACK: &str = "zk_rust_io::track(";
+pub const GUEST_TRACK: &str = "zkrust_track(";
 
 pub const OUTPUT_FUNC: &str = r"pub fn output() {";
 pub const INPUT_FUNC: &str = r"pub fn input() {";
@@ -54,12 +86,28 @@ pub fn replace(file_path: &PathBuf, search_string: &str, replace_string: &str) -
 }
 
 fn copy_dir_all(src: &impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
+    copy_dir_all_except(src, dst, &[])
+}
+
+/// Recursively copies `src` into `dst` skipping entries whose name is in `excluded`
+pub fn copy_dir_all_except(
+    src: &impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    excluded: &[&str],
+) -> io::Result<()> {
     fs::create_dir_all(&dst)?;
     for entry in fs::read_dir(src)? {
         let entry = entry?;
+        if excluded.iter().any(|name| entry.file_name() == *name) {
+            continue;
+        }
         let ty = entry.file_type()?;
         if ty.is_dir() {
-            copy_dir_all(&entry.path(), dst.as_ref().join(entry.file_name()))?;
+            copy_dir_all_except(
+                &entry.path(),
+                dst.as_ref().join(entry.file_name()),
+                excluded,
+            )?;
         } else {
             fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
         }
@@ -67,6 +115,27 @@ fn copy_dir_all(src: &impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()>
     Ok(())
 }
 
+/// Adds `dependency` under `[dependencies]` of the manifest at `toml_path` unless a dependency
+/// called `name` is already declared, as the user's dependencies are copied into the same manifest.
+pub fn add_dependency(toml_path: &Path, name: &str, dependency: &str) -> io::Result<()> {
+    let contents = fs::read_to_string(toml_path)?;
+    let manifest = contents.parse::<toml::Table>().map_err(io::Error::other)?;
+    if manifest
+        .get("dependencies")
+        .is_some_and(|dependencies| dependencies.get(name).is_some())
+    {
+        return Ok(());
+    }
+    let Some(index) = contents.find("[dependencies]") else {
+        return Err(io::Error::other(format!(
+            "Failed to find `[dependencies]` in {:?}",
+            toml_path
+        )));
+    };
+    let (before, after) = contents.split_at(index + "[dependencies]".len());
+    fs::write(toml_path, format!("{}\n{}{}", before, dependency, after))
+}
+
 pub fn insert(target_file: &str, text: &str, search_string: &str) -> io::Result<()> {
     // Read the contents of the target file
     let mut target_contents = String::new();
@@ -276,7 +345,6 @@ pub fn prepare_workspace(
 
     // Copy Cargo.toml for zkVM
     fs::copy(base_guest_toml_dir, program_toml_dir)?;
-    println!("{:?} {:?}", base_guest_toml_dir, program_toml_dir);
     fs::copy(base_host_toml_dir, host_toml_dir)?;
 
     // Select dependencies from the
@@ -284,7 +352,6 @@ pub fn prepare_workspace(
     copy_dependencies(&toml_path, program_toml_dir)?;
     copy_dependencies(&toml_path, host_toml_dir)?;
 
-    println!("something");
     Ok(())
 }
 
@@ -325,45 +392,53 @@ pub fn get_imports(filename: &PathBuf) -> io::Result<String> {
     Ok(imports)
 }
 
-pub fn extract_regex(file_path: &PathBuf, regex: &str) -> io::Result<Vec<String>> {
-    let file = fs::File::open(file_path)?;
-    let reader = io::BufReader::new(file);
-
-    let mut values = Vec::new();
-    let regex = Regex::new(regex).map_err(io::Error::other)?;
-
-    for line in reader.lines() {
-        let line = line?;
-        for cap in regex.captures_iter(&line) {
-            if let Some(matched) = cap.get(1) {
-                values.push(matched.as_str().to_string());
-            }
-        }
-    }
-
-    Ok(values)
+/// Programs declaring public inputs tag every input they write, so the guest knows which ones to commit.
+/// Writes are only rewritten in `input()`, see `lint::reject_misplaced_io_calls`.
+pub fn uses_public_inputs(input: &str) -> bool {
+    input.contains(IO_WRITE_PUBLIC)
 }
 
-//Change to remove regex and remove the marker
-pub fn remove_lines(file_path: &PathBuf, target: &str) -> io::Result<()> {
-    // Read the file line by line
-    let file = fs::File::open(file_path)?;
-    let reader = io::BufReader::new(file);
+/// `ZkRustInputs` writer of a backend, its `write_*` methods in `backend_template` after the
+/// shared definition.
+pub fn public_inputs_host(home_dir: &Path, backend_template: &str) -> io::Result<String> {
+    Ok(format!(
+        "{}{}",
+        fs::read_to_string(home_dir.join(BASE_PUBLIC_INPUTS_HOST))?,
+        fs::read_to_string(home_dir.join(backend_template))?
+    ))
+}
 
-    // Collect lines that do not contain the target string
-    let lines: Vec<String> = reader
-        .lines()
-        .map_while(Result::ok)
-        .filter(|line| !line.contains(target))
-        .collect();
+/// Adds the `ZkRustInputs` writer defined in `public_inputs_host` to the host program, and saves
+/// the public inputs metadata next to the `<backend>.pub` file in `proof_data_dir` after proving.
+pub fn add_host_public_inputs(
+    host_program: &str,
+    public_inputs_host: &str,
+    backend: &str,
+) -> String {
+    let mut host_program = host_program
+        .replace(
+            HOST_INPUT,
+            &format!("{}\n\n    {}", HOST_INPUTS_DECLARATION, HOST_INPUT),
+        )
+        .replace(
+            HOST_PUBLIC_INPUTS,
+            &format!("zkrust_inputs.save(proof_dir, \"{}\");", backend),
+        );
+    host_program.push_str(public_inputs_host);
+    host_program
+}
 
-    // Write the filtered lines back to the file
-    let mut file = fs::File::create(file_path)?;
-    for line in lines {
-        writeln!(file, "{}", line)?;
+/// Replaces the writes of `input()` with tagged ones when the program declares public inputs,
+/// otherwise private writes are plain writes.
+pub fn route_host_writes(host_program: &str, public_inputs: bool) -> String {
+    if public_inputs {
+        host_program
+            .replace(IO_WRITE_PUBLIC, HOST_TAGGED_WRITE_PUBLIC)
+            .replace(IO_WRITE_PRIVATE, HOST_TAGGED_WRITE_PRIVATE)
+            .replace(&format!("{}(", IO_WRITE), HOST_TAGGED_WRITE_PRIVATE)
+    } else {
+        host_program.replace(IO_WRITE_PRIVATE, &format!("{}(", IO_WRITE))
     }
-
-    Ok(())
 }
 
 pub fn validate_directory_structure(root: &str) -> bool {
@@ -393,28 +468,55 @@ pub fn validate_directory_structure(root: &str) -> bool {
     true
 }
 
+/// Backend replacements of the `zk_rust_io` calls of the guest
+pub struct GuestIo<'a> {
+    pub read: &'a str,
+    pub read_slice: &'a str,
+    pub commit: &'a str,
+    pub cycle_count: &'a str,
+}
+
+/// Uses the program tracking cycles with `zk_rust_io::track`
+pub fn uses_cycle_tracking(main_func_code: &str) -> bool {
+    main_func_code.contains(IO_TRACK)
+}
+
 pub fn prepare_guest(
     imports: &str,
     main_func_code: &str,
     program_header: &str,
-    io_read_header: &str,
-    io_commit_header: &str,
+    io: &GuestIo,
     guest_main_file_path: &PathBuf,
-) -> io::Result<()> {
+    original_main_file_path: &Path,
+) -> io::Result<SourceMap> {
     let mut guest_program = program_header.to_string();
     guest_program.push_str(imports);
     guest_program.push_str("pub fn main() {\n");
     guest_program.push_str(main_func_code);
     guest_program.push_str("\n}");
 
+    // Lines are only moved before the I/O replacements below, which keep line numbers intact.
+    let original = fs::read_to_string(original_main_file_path)?;
+    let mut source_map = SourceMap::new(guest_main_file_path, original_main_file_path);
+    source_map.map_lines(&guest_program, &original, imports);
+    source_map.map_snippet(&guest_program, &original, "fn main()");
+    source_map.map_snippet(&guest_program, &original, main_func_code);
+
     // Replace zkRust::read()
-    let guest_program = guest_program.replace(IO_READ, io_read_header);
+    let guest_program = guest_program.replace(IO_READ, io.read);
 
-    // Replace zkRust::commit()
-    let guest_program = guest_program.replace(IO_COMMIT, io_commit_header);
+    // Replace zkRust::read_slice()
+    let guest_program = guest_program.replace(IO_READ_SLICE, io.read_slice);
+
+    // Replace zkRust::commit() and zkRust::commit_slice()
+    let guest_program = guest_program.replace(IO_COMMIT, io.commit);
+
+    // Replace zkRust::cycle_count() and zkRust::track()
+    let guest_program = guest_program.replace(IO_CYCLE_COUNT, io.cycle_count);
+    let guest_program = guest_program.replace(IO_TRACK, GUEST_TRACK);
 
     // Write to guest
     let mut file = fs::File::create(guest_main_file_path)?;
     file.write_all(guest_program.as_bytes())?;
-    Ok(())
+    Ok(source_map)
 }
@@ -0,0 +1,166 @@
+//! `zk_rust_io::out()` decodes the values committed by a guest in commit order on every backend.
+//! The values of each example are committed as its SP1 and RISC0 guests commit them, and read back
+//! by its `output()` with the calls zkRust replaces `zk_rust_io::out()` with.
+
+use k256::EncodedPoint;
+use serde::{de::DeserializeOwned, Serialize};
+use sp1_sdk::SP1PublicValues;
+use zkRust::{risc0, sp1};
+
+/// Host side of `zk_rust_io::out()`.
+trait Out {
+    fn out<T: Serialize + DeserializeOwned>(&mut self) -> T;
+}
+
+// `sp1::SP1_HOST_READ`
+impl Out for SP1PublicValues {
+    fn out<T: Serialize + DeserializeOwned>(&mut self) -> T {
+        self.read()
+    }
+}
+
+// `risc0::RISC0_IO_OUT`, over the journal the RISC0 host template reads the receipt into
+impl<'de, R: risc0_zkvm::serde::WordRead + 'de> Out for risc0_zkvm::serde::Deserializer<'de, R> {
+    fn out<T: Serialize + DeserializeOwned>(&mut self) -> T {
+        serde::Deserialize::deserialize(self).unwrap()
+    }
+}
+
+/// Values committed by a guest, on both backends.
+#[derive(Default)]
+struct Committed {
+    // `sp1_zkvm::io::commit`
+    public_values: Vec<u8>,
+    // `risc0_zkvm::guest::env::commit`
+    journal: Vec<u8>,
+}
+
+impl Committed {
+    fn commit<T: Serialize>(&mut self, value: &T) {
+        bincode::serialize_into(&mut self.public_values, value).unwrap();
+        let words = risc0_zkvm::serde::to_vec(value).unwrap();
+        self.journal
+            .extend(words.iter().flat_map(|word| word.to_le_bytes()));
+    }
+
+    /// Runs `output` on the public values of the SP1 proof and on the RISC0 journal, asserting
+    /// both decode the same values.
+    fn assert_same_output<O, F, G>(&self, sp1_output: F, risc0_output: G) -> O
+    where
+        O: PartialEq + std::fmt::Debug,
+        F: FnOnce(&mut SP1PublicValues) -> O,
+        G: FnOnce(&mut risc0_zkvm::serde::Deserializer<'_, &[u32]>) -> O,
+    {
+        let mut public_values = SP1PublicValues::from(&self.public_values);
+        let sp1 = sp1_output(&mut public_values);
+
+        let journal_words: Vec<u32> = self
+            .journal
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let mut journal = risc0_zkvm::serde::Deserializer::new(journal_words.as_slice());
+        let risc0 = risc0_output(&mut journal);
+
+        assert_eq!(sp1, risc0);
+        sp1
+    }
+}
+
+// Both backends run the same `output()`, generic over the `out()` of the backend
+macro_rules! assert_same_output {
+    ($committed:expr, $output:ident) => {
+        $committed.assert_same_output(|out| $output(out), |out| $output(out))
+    };
+}
+
+#[test]
+fn hosts_read_with_the_tested_calls() {
+    assert_eq!(sp1::SP1_HOST_READ, "proof.public_values.read();");
+    assert_eq!(
+        risc0::RISC0_IO_OUT,
+        "serde::Deserialize::deserialize(&mut journal).unwrap();"
+    );
+    let risc0_host = include_str!("../workspaces/base_files/risc0/host");
+    assert!(risc0_host.contains("u32::from_le_bytes(word.try_into().unwrap())"));
+    assert!(risc0_host.contains("risc0_zkvm::serde::Deserializer::new(journal_words.as_slice())"));
+}
+
+// Values committed one by one are read back as a tuple
+#[test]
+fn fibonacci() {
+    fn output(out: &mut impl Out) -> (u32, u32, u32) {
+        out.out()
+    }
+    let mut committed = Committed::default();
+    committed.commit(&500u32);
+    committed.commit(&2_171_430_676u32);
+    committed.commit(&1_555_920_621u32);
+    assert_eq!(
+        assert_same_output!(committed, output),
+        (500, 2_171_430_676, 1_555_920_621)
+    );
+}
+
+#[test]
+fn is_even() {
+    fn output(out: &mut impl Out) -> (u32, bool) {
+        out.out()
+    }
+    let mut committed = Committed::default();
+    committed.commit(&1_000u32);
+    committed.commit(&true);
+    assert_eq!(assert_same_output!(committed, output), (1_000, true));
+}
+
+#[test]
+fn regex() {
+    fn output(out: &mut impl Out) -> bool {
+        out.out()
+    }
+    let mut committed = Committed::default();
+    committed.commit(&false);
+    assert!(!assert_same_output!(committed, output));
+}
+
+#[test]
+fn ecdsa() {
+    fn output(out: &mut impl Out) -> (EncodedPoint, Vec<u8>) {
+        out.out()
+    }
+    let key = EncodedPoint::from_bytes(
+        hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap(),
+    )
+    .unwrap();
+    let message = b"This is a message that will be signed, and verified within the zkVM".to_vec();
+    let committed_values = (key, message);
+    let mut committed = Committed::default();
+    committed.commit(&committed_values);
+    assert_eq!(assert_same_output!(committed, output), committed_values);
+}
+
+// Slices are committed and read back as vectors, one `out()` per committed value
+#[test]
+fn tendermint() {
+    fn output(out: &mut impl Out) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let header_hash_1: Vec<u8> = out.out();
+        let header_hash_2: Vec<u8> = out.out();
+        let verdict_encoded: Vec<u8> = out.out();
+        (header_hash_1, header_hash_2, verdict_encoded)
+    }
+    let header_hash_1 = [0x11u8; 32];
+    let header_hash_2 = [0x22u8; 32];
+    let verdict_encoded = vec![0x67, 0x53, 0x75, 0x63, 0x63, 0x65, 0x73, 0x73];
+    let mut committed = Committed::default();
+    committed.commit(&header_hash_1.as_slice());
+    committed.commit(&header_hash_2.as_slice());
+    committed.commit(&verdict_encoded.as_slice());
+    assert_eq!(
+        assert_same_output!(committed, output),
+        (
+            header_hash_1.to_vec(),
+            header_hash_2.to_vec(),
+            verdict_encoded
+        )
+    );
+}
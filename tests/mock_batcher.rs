@@ -0,0 +1,170 @@
+//! A proof submitted to `zkRust aligned mock-batcher` goes through the same path as a proof
+//! submitted to Aligned: signer, network profile, payment checks, batch inclusion and verification.
+
+use aligned_sdk::core::types::{
+    ProvingSystemId, VerificationCommitmentBatch, VerificationData, VerificationDataCommitment,
+};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::U256;
+use lambdaworks_crypto::merkle_tree::merkle::MerkleTree;
+use serde_json::Value;
+use std::{fs, path::Path, str::FromStr};
+use zkRust::{
+    config::Config,
+    mock_batcher::{MockBatcher, MockBatcherArgs},
+    network,
+    record::SubmissionRecord,
+    signer::{SignerSource, DEFAULT_DERIVATION_PATH},
+    submit_proofs_to_aligned, AlignedProof, SubmissionArgs,
+};
+
+// First account of the default anvil mnemonic
+const PRIVATE_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+const PRIVATE_KEY_ENV: &str = "ZKRUST_MOCK_BATCHER_TEST_KEY";
+const CHAIN_ID: u64 = 31337;
+
+const PROJECT_CONFIG: &str = r#"
+network = "local"
+
+[networks.local]
+chain-id = 31337
+batcher-payment-service = "0x7969c5eD335650692Bc04293B07F5BF2e7A673C0"
+aligned-service-manager = "0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8"
+"#;
+
+// Starts the mock on free ports, returns its RPC and batcher URLs.
+async fn start_mock(skip_verification: bool) -> (String, String) {
+    let mock = MockBatcher::bind(&MockBatcherArgs {
+        port: 0,
+        rpc_port: 0,
+        chain_id: CHAIN_ID,
+        batch_interval: 100,
+        skip_verification,
+    })
+    .await
+    .unwrap();
+    let urls = (mock.rpc_url().unwrap(), mock.batcher_url().unwrap());
+    tokio::spawn(mock.serve());
+    urls
+}
+
+// Submission to the `[networks.local]` profile of a zkrust.toml in `dir`.
+fn submission_args(dir: &Path, rpc_url: String, batcher_url: String) -> SubmissionArgs {
+    fs::write(dir.join("zkrust.toml"), PROJECT_CONFIG).unwrap();
+    let config = Config::load(&dir.join("home"), dir).unwrap();
+    std::env::set_var(PRIVATE_KEY_ENV, PRIVATE_KEY);
+    SubmissionArgs {
+        signer: SignerSource::PrivateKeyEnv(PRIVATE_KEY_ENV.to_string()),
+        derivation_path: DEFAULT_DERIVATION_PATH.to_string(),
+        keystore_path: None,
+        rpc_url,
+        network: config.network.unwrap(),
+        batcher_payment: 4_000_000_000_000_000,
+        batch_inclusion_data_directory_path: dir
+            .join("aligned_verification_data")
+            .display()
+            .to_string(),
+        batcher_url,
+        retries: 0,
+        submit_timeout: 30,
+        verification_timeout: 30,
+        assume_yes: true,
+        network_profiles: config.networks,
+    }
+}
+
+// Not a valid SP1 proof, only accepted by a mock skipping verification
+fn sp1_proof(dir: &Path) -> AlignedProof {
+    AlignedProof {
+        path: dir.join("sp1.proof"),
+        proving_system: ProvingSystemId::SP1,
+        proof: b"proof".to_vec(),
+        program: b"elf".to_vec(),
+        pub_input: Some(b"public values".to_vec()),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn submits_a_proof_to_the_mock_batcher() {
+    let (rpc_url, batcher_url) = start_mock(true).await;
+    let dir = tempfile::tempdir().unwrap();
+    let args = submission_args(dir.path(), rpc_url.clone(), batcher_url);
+    let proof = sp1_proof(dir.path());
+
+    // The batch of a single proof is the leaf of its commitment
+    let verification_data = VerificationData {
+        proving_system: proof.proving_system,
+        proof: proof.proof.clone(),
+        proof_generator_addr: LocalWallet::from_str(PRIVATE_KEY).unwrap().address(),
+        vm_program_code: Some(proof.program.clone()),
+        verification_key: None,
+        pub_input: proof.pub_input.clone(),
+    };
+    let commitment = VerificationDataCommitment::from(&verification_data);
+    let expected_root = MerkleTree::<VerificationCommitmentBatch>::build(&[commitment.clone()])
+        .unwrap()
+        .root;
+
+    let submissions = submit_proofs_to_aligned(vec![proof], &args).await.unwrap();
+    assert_eq!(submissions.len(), 1);
+    let submission = &submissions[0];
+    assert_eq!(
+        submission.batch_merkle_root,
+        format!("0x{}", hex::encode(expected_root))
+    );
+    assert_eq!(submission.explorer_url, None);
+
+    let saved: Value =
+        serde_json::from_slice(&fs::read(&submission.verification_data_path).unwrap()).unwrap();
+    assert_eq!(saved["batch_merkle_root"], hex::encode(expected_root));
+    assert_eq!(
+        saved["proof_commitment"],
+        hex::encode(commitment.proof_commitment)
+    );
+    assert_eq!(saved["pub_input"], hex::encode(b"public values"));
+    assert_eq!(saved["verification_data_batch_index"], 0);
+
+    let record = SubmissionRecord::read(&submission.record).unwrap();
+    assert!(record.verified);
+    assert_eq!(record.nonce, U256::zero());
+    let aligned_verification_data = record.aligned_verification_data().unwrap().unwrap();
+    let network = network::resolve(&args.network, &args.network_profiles).unwrap();
+    assert!(network
+        .is_verified(&rpc_url, &aligned_verification_data)
+        .await
+        .unwrap());
+
+    // verifyBatchInclusion rebuilds the leaf of the proof and checks it against the root
+    let mut other_root = record.aligned_verification_data().unwrap().unwrap();
+    other_root.batch_merkle_root = [0; 32];
+    let mut other_proof = record.aligned_verification_data().unwrap().unwrap();
+    other_proof.verification_data_commitment.proof_commitment[0] ^= 1;
+    let mut other_input = record.aligned_verification_data().unwrap().unwrap();
+    other_input
+        .verification_data_commitment
+        .pub_input_commitment[0] ^= 1;
+    for tampered in [other_root, other_proof, other_input] {
+        assert!(!network.is_verified(&rpc_url, &tampered).await.unwrap());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_a_proof_that_does_not_verify() {
+    let (rpc_url, batcher_url) = start_mock(false).await;
+    let dir = tempfile::tempdir().unwrap();
+    let args = submission_args(dir.path(), rpc_url.clone(), batcher_url);
+
+    assert!(submit_proofs_to_aligned(vec![sp1_proof(dir.path())], &args)
+        .await
+        .is_err());
+    // A rejected proof does not use up its nonce
+    let network = network::resolve(&args.network, &args.network_profiles).unwrap();
+    let address = LocalWallet::from_str(PRIVATE_KEY).unwrap().address();
+    assert_eq!(
+        network.next_nonce(&rpc_url, address).await.unwrap(),
+        U256::zero()
+    );
+    let record =
+        SubmissionRecord::read(&SubmissionRecord::path(&dir.path().join("sp1.proof"))).unwrap();
+    assert!(record.inclusion.is_none());
+}
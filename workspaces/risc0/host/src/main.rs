@@ -1,33 +1,209 @@
 // These constants represent the RISC-V ELF and the image ID generated by risc0-build.
 // The ELF is used for proving and the ID is used for verification.
 use methods::{METHOD_ELF, METHOD_ID};
-use risc0_zkvm::{default_prover, ExecutorEnv};
+use risc0_zkvm::{default_executor, default_prover, ExecutorEnv};
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     let current_dir = std::path::PathBuf::from(args[1].clone());
+    let (method_elf, method_id) = zkrust_method();
 
-    // INPUT //
+    // Obtain the default prover, reused for every input.
+    let prover = default_prover();
 
-    let env = ExecutorEnv::builder().build().unwrap();
+    for zkrust_run in zkrust_runs(&current_dir, "risc0") {
+        let proof_dir = &zkrust_run.proof_dir;
+        // Setup the inputs.
+        let mut stdin = Risc0Stdin::default();
 
-    // Obtain the default prover.
-    let prover = default_prover();
+        // INPUT //
+
+        let mut cycle_tracker = ZkRustCycleTracker::default();
+        let env = ExecutorEnv::builder()
+            .write_slice(&stdin.bytes)
+            .stdout(&mut cycle_tracker)
+            .build()
+            .unwrap();
+
+        // `zkRust profile` and `zkRust precompiles` only execute the program. When profiling,
+        // the executor writes the profile to RISC0_PPROF_OUT.
+        if std::env::var_os("ZKRUST_EXECUTE").is_some() {
+            let session = default_executor().execute(env, &method_elf).unwrap();
+            cycle_tracker.report();
+            let cycles: u64 = session.segments.iter().map(|segment| segment.cycles as u64).sum();
+            std::fs::create_dir_all(proof_dir).expect("Failed to create proof_data/risc0");
+            std::fs::write(proof_dir.join("risc0.execute_cycles"), cycles.to_string()).expect("Failed to create Risc0 cycle count file");
+            println!("Executed in {} cycles", cycles);
+            continue;
+        }
+
+        // Produce a receipt by proving the specified ELF binary.
+        let prove_info = prover.prove(env, &method_elf).unwrap();
+        let receipt = prove_info.receipt;
+        cycle_tracker.report();
+
+        receipt.verify(method_id).unwrap();
+
+        // Read the committed values back in the order they were committed, like SP1 public values.
+        let journal_words: Vec<u32> = receipt
+            .journal
+            .bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        #[allow(unused_mut, unused_variables)]
+        let mut journal = risc0_zkvm::serde::Deserializer::new(journal_words.as_slice());
+
+        // OUTPUT //
+
+        let serialized = bincode::serialize(&receipt).unwrap();
 
-    // Produce a receipt by proving the specified ELF binary.
-    let receipt = prover.prove(env, METHOD_ELF).unwrap().receipt;
+        //TODO(pat): remove expects
+        std::fs::create_dir_all(proof_dir).expect("Failed to create proof_data/risc0");
+        std::fs::write(proof_dir.join("risc0.proof"), &serialized).expect("Failed to create Risc0 proof file");
+        std::fs::write(proof_dir.join("risc0.imageid"), &convert(&method_id)).expect("Failed to create Risc0 Image ID file");
+        std::fs::write(proof_dir.join("risc0.pub"), &receipt.journal.bytes).expect("Failed to create Risc0 public input file");
+        std::fs::write(proof_dir.join("risc0.cycles"), prove_info.stats.total_cycles.to_string()).expect("Failed to create Risc0 cycle count file");
 
-    receipt.verify(METHOD_ID).unwrap();
+        // PUBLIC INPUTS //
+    }
+}
 
-    // OUTPUT //
+/// Guest ELF and image ID. zkRust skips building the guest when they are in its cache, then
+/// `METHOD_ELF` is empty and they are read from the cache instead.
+fn zkrust_method() -> (Vec<u8>, [u32; 8]) {
+    let Some(cache_dir) = std::env::var_os("ZKRUST_CACHE_DIR").map(std::path::PathBuf::from) else {
+        return (METHOD_ELF.to_vec(), METHOD_ID);
+    };
+    let (elf_path, id_path) = (cache_dir.join("risc0.elf"), cache_dir.join("risc0.imageid"));
+    if let (Ok(elf), Ok(id)) = (std::fs::read(&elf_path), std::fs::read(&id_path)) {
+        if id.len() == 32 {
+            let mut method_id = [0u32; 8];
+            for (word, bytes) in method_id.iter_mut().zip(id.chunks_exact(4)) {
+                *word = u32::from_le_bytes(bytes.try_into().unwrap());
+            }
+            return (elf, method_id);
+        }
+    }
+
+    std::fs::write(&elf_path, METHOD_ELF).expect("Failed to cache Risc0 elf");
+    // zkRust skips the build once the image ID exists, so it is written last and whole
+    let id_tmp_path = cache_dir.join("risc0.imageid.tmp");
+    std::fs::write(&id_tmp_path, convert(&METHOD_ID)).expect("Failed to cache Risc0 image ID");
+    std::fs::rename(&id_tmp_path, &id_path).expect("Failed to cache Risc0 image ID");
+    (METHOD_ELF.to_vec(), METHOD_ID)
+}
 
-    let serialized = bincode::serialize(&receipt).unwrap();
+/// Input file and proof data directory of one proof.
+struct ZkRustRun {
+    // Only read by programs proven over input files
+    #[allow(dead_code)]
+    input: Option<std::path::PathBuf>,
+    proof_dir: std::path::PathBuf,
+}
+
+/// `zkRust prove --inputs-dir` passes one input file per proof, each proven into
+/// `proof_data/<input name>/<backend>`. Other runs prove once into `proof_data/<backend>`.
+fn zkrust_runs(current_dir: &std::path::Path, backend: &str) -> Vec<ZkRustRun> {
+    let proof_data_dir = current_dir.join("proof_data");
+    match std::env::var_os("ZKRUST_INPUTS") {
+        Some(inputs) => std::env::split_paths(&inputs)
+            .map(|input| ZkRustRun {
+                proof_dir: proof_data_dir
+                    .join(input.file_stem().expect("Input files must have a name"))
+                    .join(backend),
+                input: Some(input),
+            })
+            .collect(),
+        None => vec![ZkRustRun {
+            input: std::env::var_os("ZKRUST_INPUT").map(std::path::PathBuf::from),
+            proof_dir: proof_data_dir.join(backend),
+        }],
+    }
+}
 
-    //TODO(pat): remove expects
-    std::fs::create_dir_all(current_dir.join("proof_data/risc0")).expect("Failed to create proof_data/sp1");
-    std::fs::write(&current_dir.join("proof_data/risc0/risc0.proof"), &serialized).expect("Failed to create Risc0 proof file");
-    std::fs::write(&current_dir.join("proof_data/risc0/risc0.imageid"), &convert(&METHOD_ID)).expect("Failed to create Risc0 Image ID file");
-    std::fs::write(&current_dir.join("proof_data/risc0/risc0.pub"), &receipt.journal.bytes).expect("Failed to create Risc0 public input file");
+/// Serializes the values written by `input()` in order, for the guest to read them back.
+#[derive(Default)]
+struct Risc0Stdin {
+    bytes: Vec<u8>,
+}
+
+impl Risc0Stdin {
+    fn write<T: serde::Serialize + ?Sized>(&mut self, value: &T) {
+        let words = risc0_zkvm::serde::to_vec(value).expect("Failed to serialize input");
+        self.bytes.extend(words.iter().flat_map(|word| word.to_le_bytes()));
+    }
+
+    // Raw bytes are length prefixed and padded to a whole number of words, so the values
+    // written after them stay aligned.
+    fn write_slice(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u32));
+        self.bytes.extend_from_slice(bytes);
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+    }
+}
+
+/// Guest stdout. Collects the cycles reported by `zk_rust_io::track` and forwards the other lines.
+#[derive(Default)]
+struct ZkRustCycleTracker {
+    line: Vec<u8>,
+    // Label, cycles and number of calls, in the order the labels were first reported
+    labels: Vec<(String, u64, usize)>,
+}
+
+impl std::io::Write for ZkRustCycleTracker {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for byte in buf {
+            self.line.push(*byte);
+            if *byte == b'\n' {
+                self.flush_line();
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::Write::flush(&mut std::io::stdout())
+    }
+}
+
+impl ZkRustCycleTracker {
+    fn flush_line(&mut self) {
+        let line = String::from_utf8_lossy(&self.line).to_string();
+        self.line.clear();
+        let tracked = line
+            .trim_end()
+            .strip_prefix("zkrust-cycle-tracker: ")
+            .and_then(|tracked| tracked.rsplit_once(": "))
+            .and_then(|(label, cycles)| Some((label.to_string(), cycles.parse::<u64>().ok()?)));
+        let Some((label, cycles)) = tracked else {
+            print!("{}", line);
+            return;
+        };
+        match self.labels.iter_mut().find(|(tracked, _, _)| *tracked == label) {
+            Some((_, total, calls)) => {
+                *total += cycles;
+                *calls += 1;
+            }
+            None => self.labels.push((label, cycles, 1)),
+        }
+    }
+
+    /// Prints the cycles of each label tracked during execution.
+    fn report(&mut self) {
+        if !self.line.is_empty() {
+            self.line.push(b'\n');
+            self.flush_line();
+        }
+        if self.labels.is_empty() {
+            return;
+        }
+        let width = self.labels.iter().map(|(label, _, _)| label.len()).fold("label".len(), usize::max);
+        println!("{:<width$}  {:>12}  {:>6}", "label", "cycles", "calls");
+        for (label, cycles, calls) in &self.labels {
+            println!("{:<width$}  {:>12}  {:>6}", label, cycles, calls);
+        }
+    }
 }
 
 pub fn convert(data: &[u32; 8]) -> [u8; 32] {
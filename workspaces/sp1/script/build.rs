@@ -1,5 +1,9 @@
 use sp1_helper::build_program;
 
 fn main() {
-    build_program("../program")
+    // zkRust restores the ELF from its cache when the program did not change
+    println!("cargo:rerun-if-env-changed=ZKRUST_SKIP_PROGRAM_BUILD");
+    if std::env::var_os("ZKRUST_SKIP_PROGRAM_BUILD").is_none() {
+        build_program("../program")
+    }
 }
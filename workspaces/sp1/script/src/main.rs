@@ -1,4 +1,4 @@
-use sp1_sdk::{ProverClient, SP1Stdin};
+use sp1_sdk::{HashableKey, ProverClient, SP1ProvingKey, SP1Stdin, SP1VerifyingKey};
 
 /// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
 ///
@@ -11,28 +11,114 @@ fn main() {
     // Setup the logger.
     sp1_sdk::utils::setup_logger();
 
-    // Setup the inputs.
-    let mut stdin = SP1Stdin::new();
+    // The proving key is set up once and reused for every input.
+    let client = ProverClient::new();
+    let (pk, vk) = zkrust_setup(&client);
 
-    // INPUT //
+    for zkrust_run in zkrust_runs(&current_dir, "sp1") {
+        let proof_dir = &zkrust_run.proof_dir;
+        // Setup the inputs.
+        let mut stdin = SP1Stdin::new();
+
+        // INPUT //
+
+        // Execute the program to record its cycle count.
+        let (_, report) = client
+            .execute(METHOD_ELF, stdin.clone())
+            .run()
+            .expect("failed to execute program");
+
+        // `zkRust profile` and `zkRust precompiles` only execute the program. When profiling,
+        // the executor writes the trace to TRACE_FILE.
+        if std::env::var_os("ZKRUST_EXECUTE").is_some() {
+            std::fs::create_dir_all(proof_dir).expect("Failed to create proof_data/sp1");
+            std::fs::write(
+                proof_dir.join("sp1.execute_cycles"),
+                report.total_instruction_count().to_string(),
+            )
+            .expect("Failed to save SP1 cycle count");
+            println!("Executed in {} cycles", report.total_instruction_count());
+            continue;
+        }
+
+        let mut proof = client
+            .prove(&pk, stdin)
+            .compressed()
+            .run()
+            .expect("failed to generate proof");
+
+        // OUTPUT //
+
+        // Verify the proof.
+        client.verify(&proof, &vk).expect("Failed to verify proof");
+
+        std::fs::create_dir_all(proof_dir).expect("Failed to create proof_data/sp1");
+        let proof_data = bincode::serialize(&proof).expect("Failed to serialize proof");
+        std::fs::write(proof_dir.join("sp1.proof"), proof_data).expect("Failed to save SP1 Proof file");
+        std::fs::write(proof_dir.join("sp1.elf"), METHOD_ELF).expect("Failed to create SP1 elf file");
+        std::fs::write(proof_dir.join("sp1.pub"), proof.public_values)
+            .expect("Failed to save SP1 public input");
+        std::fs::write(proof_dir.join("sp1.vkey"), vk.bytes32())
+            .expect("Failed to save SP1 verification key hash");
+        std::fs::write(
+            proof_dir.join("sp1.cycles"),
+            report.total_instruction_count().to_string(),
+        )
+        .expect("Failed to save SP1 cycle count");
+
+        // PUBLIC INPUTS //
+    }
+}
+
+/// Proving and verifying keys, read from the zkRust cache when the program did not change
+/// since they were set up.
+fn zkrust_setup(client: &ProverClient) -> (SP1ProvingKey, SP1VerifyingKey) {
+    let Some(cache_dir) = std::env::var_os("ZKRUST_CACHE_DIR").map(std::path::PathBuf::from) else {
+        return client.setup(METHOD_ELF);
+    };
+    let (pk_path, vk_path) = (cache_dir.join("sp1.pk"), cache_dir.join("sp1.vk"));
+    if let (Ok(pk), Ok(vk)) = (std::fs::read(&pk_path), std::fs::read(&vk_path)) {
+        if let (Ok(pk), Ok(vk)) = (bincode::deserialize(&pk), bincode::deserialize(&vk)) {
+            return (pk, vk);
+        }
+    }
 
-    let client = ProverClient::new();
     let (pk, vk) = client.setup(METHOD_ELF);
-    let mut proof = client
-        .prove(&pk, stdin)
-        .compressed()
-        .run()
-        .expect("failed to generate proof");
-
-    // OUTPUT //
-
-    // Verify the proof.
-    client.verify(&proof, &vk).expect("Failed to verify proof");
-
-    std::fs::create_dir_all(&current_dir.join("proof_data/sp1")).expect("Failed to create proof_data/sp1");
-    let proof_data = bincode::serialize(&proof).expect("Failed to serialize proof");
-    std::fs::write(&current_dir.join("proof_data/sp1/sp1.proof"), proof_data).expect("Failed to save SP1 Proof file");
-    std::fs::write(&current_dir.join("proof_data/sp1/sp1.elf"), METHOD_ELF).expect("Failed to create SP1 elf file");
-    std::fs::write(&current_dir.join("proof_data/sp1/sp1_fibonacci.pub"), proof.public_values)
-        .expect("Failed to save SP1 public input");
+    std::fs::write(cache_dir.join("sp1.elf"), METHOD_ELF).expect("Failed to cache SP1 elf");
+    std::fs::write(&vk_path, bincode::serialize(&vk).expect("Failed to serialize verifying key"))
+        .expect("Failed to cache SP1 verifying key");
+    // zkRust skips the build once the proving key exists, so it is written last and whole
+    let pk_tmp_path = cache_dir.join("sp1.pk.tmp");
+    std::fs::write(&pk_tmp_path, bincode::serialize(&pk).expect("Failed to serialize proving key"))
+        .expect("Failed to cache SP1 proving key");
+    std::fs::rename(&pk_tmp_path, &pk_path).expect("Failed to cache SP1 proving key");
+    (pk, vk)
+}
+
+/// Input file and proof data directory of one proof.
+struct ZkRustRun {
+    // Only read by programs proven over input files
+    #[allow(dead_code)]
+    input: Option<std::path::PathBuf>,
+    proof_dir: std::path::PathBuf,
+}
+
+/// `zkRust prove --inputs-dir` passes one input file per proof, each proven into
+/// `proof_data/<input name>/<backend>`. Other runs prove once into `proof_data/<backend>`.
+fn zkrust_runs(current_dir: &std::path::Path, backend: &str) -> Vec<ZkRustRun> {
+    let proof_data_dir = current_dir.join("proof_data");
+    match std::env::var_os("ZKRUST_INPUTS") {
+        Some(inputs) => std::env::split_paths(&inputs)
+            .map(|input| ZkRustRun {
+                proof_dir: proof_data_dir
+                    .join(input.file_stem().expect("Input files must have a name"))
+                    .join(backend),
+                input: Some(input),
+            })
+            .collect(),
+        None => vec![ZkRustRun {
+            input: std::env::var_os("ZKRUST_INPUT").map(std::path::PathBuf::from),
+            proof_dir: proof_data_dir.join(backend),
+        }],
+    }
 }
@@ -1,12 +1,50 @@
 use serde::{de::DeserializeOwned, Serialize};
+use std::path::PathBuf;
 
 #[inline(never)]
 pub fn read<T: DeserializeOwned + Default>() -> T {
     T::default()
 }
+/// Reads raw bytes written with `write_slice`, without going through serde.
+#[inline(never)]
+pub fn read_slice() -> Vec<u8> {
+    Vec::new()
+}
 #[inline(never)]
 pub fn commit<T: Serialize>(_value: &T) {}
+/// Commits raw bytes to the public values, without going through serde.
+#[inline(never)]
+pub fn commit_slice(_buf: &[u8]) {}
 #[inline(never)]
 pub fn write<T: Serialize>(_buf: &T) {}
+/// Writes raw bytes for the guest to read with `read_slice`, without going through serde.
+#[inline(never)]
+pub fn write_slice(_buf: &[u8]) {}
+/// Same as `write`, the value is only known to the prover.
+#[inline(never)]
+pub fn write_private<T: Serialize>(_buf: &T) {}
+/// Writes a value that is committed after the program outputs, so the proof is bound to it.
+#[inline(never)]
+pub fn write_public<T: Serialize>(_buf: &T) {}
 #[inline(never)]
 pub fn out() {}
+/// Arguments passed after `--` to `zkRust prove-*`, available in `input()` and `output()`.
+#[inline(never)]
+pub fn host_args() -> Vec<String> {
+    Vec::new()
+}
+/// Root directory of the project, for reading data files in `input()` and `output()`.
+#[inline(never)]
+pub fn project_dir() -> PathBuf {
+    PathBuf::new()
+}
+/// Cycles executed by the guest so far. Always 0 on SP1, whose guests cannot read it.
+#[inline(never)]
+pub fn cycle_count() -> u64 {
+    0
+}
+/// Runs `f` and reports the cycles it took under `label` once the guest has executed.
+#[inline(never)]
+pub fn track<T>(_label: &str, f: impl FnOnce() -> T) -> T {
+    f()
+}